@@ -0,0 +1,575 @@
+use arboard::Clipboard;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::export::{self, ExportFilter, ExportFormat};
+use crate::history::ClipboardHistory;
+use crate::import::{self, ImportSource};
+use crate::ipc;
+use crate::pause::Pause;
+use crate::picker;
+use crate::preferences::Config;
+use crate::window::{WindowMessage, WindowSender};
+use crate::window_lock::WindowLock;
+
+const USAGE: &str = "Usage: clipstash [COMMAND]
+
+Commands:
+  show            Show the window of the running instance
+  popup           Show the compact popup of the running instance at the mouse
+                  cursor, or hide it if it's already shown
+  list            List the clipboard history, one item per line with its id
+  search <QUERY>  List the history items containing the query
+  copy <ID>       Copy a history item to the clipboard
+  remove <ID>     Remove a history item
+  pin <ID>        Pin a history item, so it's kept when clearing the history
+  unpin <ID>      Unpin a history item
+  tag <ID> <TAG>  Add a tag to a history item
+  untag <ID> <TAG>
+                  Remove a tag from a history item
+  clear           Clear the clipboard history, except for pinned items
+  export <FORMAT> [OPTIONS]
+                  Export the history as jsonl, csv or md (Markdown), to stdout
+                  or a file. Options: --output <FILE>, --since <DATE>,
+                  --until <DATE> (exclusive), --tag <TAG>, --pinned
+  import <SOURCE> [FILE]
+                  Import the history of another clipboard manager, skipping
+                  items already in the history. Sources: copyq, clipman,
+                  gpaste, ditto, clipstash (a jsonl export), lines (one item
+                  per line) and nul (NUL separated items)
+  pick            Pick an item to copy with the launcher set in the preferences
+  pick --list     Print the items to pick from, newest first, for piping into a launcher
+  pick --select   Copy the item picked from the list, read from stdin
+  pause [MINUTES] Stop capturing the clipboard, until resumed or for some minutes
+  resume          Start capturing the clipboard again
+  lock            Lock the window of the running instance with the lock passphrase
+  unlock          Unlock the encrypted history or the locked window of the
+                  running instance, with the passphrase read from stdin
+  quit            Quit the running instance
+  help            Print this message
+
+Options:
+  --daemon        Run without a window or tray icon, only capturing the clipboard
+
+Without a command, ClipStash starts (or shows the already running instance).";
+
+// Where a command is being executed
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+pub enum Context<'a> {
+    // In this process, on the history file, because no instance is running
+    Local,
+    // In a running instance started with --daemon
+    Daemon,
+    // In a running instance with a window
+    Window(&'a WindowSender),
+}
+
+pub enum Command {
+    Show,
+    Popup,
+    List,
+    Search(String),
+    Copy(u64),
+    Remove(u64),
+    Pin(u64),
+    Unpin(u64),
+    Tag(u64, String),
+    Untag(u64, String),
+    Clear,
+    Export {
+        format: ExportFormat,
+        filter: ExportFilter,
+        // Absolute path, since the running instance may have another working directory
+        output: Option<PathBuf>,
+    },
+    Import {
+        source: ImportSource,
+        path: Option<PathBuf>,
+    },
+    // Minutes to pause for, None to pause until resumed
+    Pause(Option<u64>),
+    Resume,
+    Lock,
+    Unlock(String),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        let parse_id = |id: Option<&&str>| -> Result<u64, String> {
+            let id = id.ok_or("Missing item id")?;
+            id.parse().map_err(|_| format!("Invalid item id: {}", id))
+        };
+        let parse_tag = |tag: Option<&&str>| -> Result<String, String> {
+            tag.map(|tag| tag.to_string())
+                .ok_or("Missing tag".to_string())
+        };
+
+        match args.first() {
+            Some(&"show") => Ok(Command::Show),
+            Some(&"popup") => Ok(Command::Popup),
+            Some(&"list") => Ok(Command::List),
+            Some(&"search") if args.len() > 1 => Ok(Command::Search(args[1..].join(" "))),
+            Some(&"search") => Err("Missing search query".to_string()),
+            Some(&"copy") => Ok(Command::Copy(parse_id(args.get(1))?)),
+            Some(&"remove") => Ok(Command::Remove(parse_id(args.get(1))?)),
+            Some(&"pin") => Ok(Command::Pin(parse_id(args.get(1))?)),
+            Some(&"unpin") => Ok(Command::Unpin(parse_id(args.get(1))?)),
+            Some(&"tag") => Ok(Command::Tag(
+                parse_id(args.get(1))?,
+                parse_tag(args.get(2))?,
+            )),
+            Some(&"untag") => Ok(Command::Untag(
+                parse_id(args.get(1))?,
+                parse_tag(args.get(2))?,
+            )),
+            Some(&"clear") => Ok(Command::Clear),
+            Some(&"export") => Self::parse_export(&args[1..]),
+            Some(&"import") => {
+                let source = ImportSource::from_name(args.get(1).ok_or("Missing import source")?)?;
+                let path = match args.get(2) {
+                    Some(path) => Some(std::path::absolute(path).map_err(|e| e.to_string())?),
+                    None => None,
+                };
+                Ok(Command::Import { source, path })
+            }
+            Some(&"pause") => match args.get(1) {
+                Some(minutes) => {
+                    let minutes = minutes
+                        .parse()
+                        .ok()
+                        .filter(|&minutes| minutes > 0)
+                        .ok_or(format!("Invalid number of minutes: {}", minutes))?;
+                    Ok(Command::Pause(Some(minutes)))
+                }
+                None => Ok(Command::Pause(None)),
+            },
+            Some(&"resume") => Ok(Command::Resume),
+            Some(&"lock") => Ok(Command::Lock),
+            Some(&"unlock") => Ok(Command::Unlock(args[1..].join(" "))),
+            Some(&"quit") => Ok(Command::Quit),
+            Some(command) => Err(format!("Unknown command: {}", command)),
+            None => Err("Missing command".to_string()),
+        }
+    }
+
+    fn parse_export(args: &[&str]) -> Result<Self, String> {
+        let format = ExportFormat::from_name(args.first().ok_or("Missing export format")?)?;
+        let mut filter = ExportFilter::default();
+        let mut output = None;
+
+        let mut args = args[1..].iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match *arg {
+                "--output" => {
+                    let path = std::path::absolute(value()?).map_err(|e| e.to_string())?;
+                    output = Some(path);
+                }
+                "--since" => filter.since = Some(export::parse_date(value()?)?),
+                "--until" => filter.until = Some(export::parse_date(value()?)?),
+                "--tag" => filter.tag = Some(value()?.to_string()),
+                "--pinned" => filter.pinned_only = true,
+                _ => return Err(format!("Unknown export option: {}", arg)),
+            }
+        }
+
+        Ok(Command::Export {
+            format,
+            filter,
+            output,
+        })
+    }
+
+    // Commands are sent over IPC as a single line of space separated words
+    #[cfg(unix)]
+    pub fn from_line(line: &str) -> Result<Self, String> {
+        let args: Vec<String> = line.split(' ').map(unescape_line).collect();
+        Self::parse(&args)
+    }
+
+    #[cfg(unix)]
+    pub fn to_line(&self) -> String {
+        let args: Vec<String> = match self {
+            Command::Show => vec!["show".to_string()],
+            Command::Popup => vec!["popup".to_string()],
+            Command::List => vec!["list".to_string()],
+            Command::Search(query) => vec!["search".to_string(), query.clone()],
+            Command::Copy(id) => vec!["copy".to_string(), id.to_string()],
+            Command::Remove(id) => vec!["remove".to_string(), id.to_string()],
+            Command::Pin(id) => vec!["pin".to_string(), id.to_string()],
+            Command::Unpin(id) => vec!["unpin".to_string(), id.to_string()],
+            Command::Tag(id, tag) => vec!["tag".to_string(), id.to_string(), tag.clone()],
+            Command::Untag(id, tag) => vec!["untag".to_string(), id.to_string(), tag.clone()],
+            Command::Clear => vec!["clear".to_string()],
+            Command::Export {
+                format,
+                filter,
+                output,
+            } => {
+                let mut args = vec!["export".to_string(), format.name().to_string()];
+                if let Some(output) = output {
+                    args.extend(["--output".to_string(), output.display().to_string()]);
+                }
+                if let Some(since) = filter.since {
+                    args.extend(["--since".to_string(), export::format_date(since)]);
+                }
+                if let Some(until) = filter.until {
+                    args.extend(["--until".to_string(), export::format_date(until)]);
+                }
+                if let Some(tag) = &filter.tag {
+                    args.extend(["--tag".to_string(), tag.clone()]);
+                }
+                if filter.pinned_only {
+                    args.push("--pinned".to_string());
+                }
+                args
+            }
+            Command::Import { source, path } => {
+                let mut args = vec!["import".to_string(), source.name().to_string()];
+                if let Some(path) = path {
+                    args.push(path.display().to_string());
+                }
+                args
+            }
+            Command::Pause(minutes) => {
+                let mut args = vec!["pause".to_string()];
+                args.extend(minutes.map(|minutes| minutes.to_string()));
+                args
+            }
+            Command::Resume => vec!["resume".to_string()],
+            Command::Lock => vec!["lock".to_string()],
+            Command::Unlock(passphrase) => vec!["unlock".to_string(), passphrase.clone()],
+            Command::Quit => vec!["quit".to_string()],
+        };
+
+        // Spaces are escaped too, so that arguments can contain them
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| escape_line(arg).replace(' ', "\\s"))
+            .collect();
+        args.join(" ")
+    }
+}
+
+// Runs a command line invocation and returns the process exit code.
+// Commands are forwarded to the running instance if there is one,
+// otherwise they operate on the history file directly.
+pub fn run(args: &[String]) -> i32 {
+    if args[0] == "help" || args[0] == "--help" {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let result = if args[0] == "pick" {
+        picker::run(&args[1..])
+    } else if args[0] == "unlock" {
+        // Read the passphrase from stdin, so it doesn't end up in the shell history
+        read_passphrase().and_then(|passphrase| dispatch(&Command::Unlock(passphrase)))
+    } else {
+        match Command::parse(args) {
+            Ok(command) => dispatch(&command),
+            Err(message) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                return 2;
+            }
+        }
+    };
+
+    match result {
+        Ok(output) => {
+            print!("{}", output);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
+
+// Sends the command to the running instance, or executes it here if there's none.
+// The instance lock is held meanwhile, so no instance starts and overwrites the
+// history file under the command. An instance that holds the lock but can't be
+// reached (e.g. on Windows, which has no IPC yet) must not be written behind.
+pub fn dispatch(command: &Command) -> Result<String, String> {
+    if let Some(result) = ipc::send_command(command) {
+        return result;
+    }
+    match ipc::InstanceLock::acquire() {
+        Some(_instance_lock) => execute(command, &Context::Local),
+        None => Err("ClipStash is running but not reachable".to_string()),
+    }
+}
+
+pub fn execute(command: &Command, context: &Context) -> Result<String, String> {
+    let needs_history = !matches!(
+        command,
+        Command::Show
+            | Command::Popup
+            | Command::Pause(_)
+            | Command::Resume
+            | Command::Lock
+            | Command::Unlock(_)
+            | Command::Quit
+    );
+    if needs_history {
+        if let Context::Window(_) = context {
+            // The window may have been idle long enough without being redrawn
+            let lock_after_minutes = Config::get_instance().get_lock_after_minutes();
+            if WindowLock::get_instance().lock_if_idle(lock_after_minutes) {
+                ClipboardHistory::get_instance().clear_undo();
+            }
+        }
+        check_access()?;
+    }
+
+    match command {
+        Command::Show | Command::Popup => match context {
+            Context::Local => Err("ClipStash is not running".to_string()),
+            Context::Daemon => {
+                Err("ClipStash is running as a daemon, without a window".to_string())
+            }
+            Context::Window(window) => {
+                window.send(match command {
+                    Command::Popup => WindowMessage::Popup,
+                    _ => WindowMessage::Show,
+                });
+                Ok(String::new())
+            }
+        },
+        Command::Pause(_) | Command::Resume => {
+            // Only the running instance captures the clipboard
+            if let Context::Local = context {
+                return Err("ClipStash is not running".to_string());
+            }
+
+            let mut pause = Pause::get_instance();
+            match command {
+                Command::Pause(minutes) => {
+                    pause.pause(minutes.map(|minutes| Duration::from_secs(minutes * 60)))
+                }
+                _ => pause.resume(),
+            }
+            if let Context::Window(window) = context {
+                window.send(WindowMessage::Refresh);
+            }
+            Ok(format!("{}\n", pause.describe()))
+        }
+        Command::Lock => match context {
+            Context::Local => Err("ClipStash is not running".to_string()),
+            Context::Daemon => {
+                Err("ClipStash is running as a daemon, without a window".to_string())
+            }
+            Context::Window(window) => {
+                if !Config::get_instance().has_lock_passphrase() {
+                    return Err("No lock passphrase is set, set one from \
+                         Edit > Lock Passphrase... in the window"
+                        .to_string());
+                }
+                WindowLock::get_instance().lock();
+                ClipboardHistory::get_instance().clear_undo();
+                window.send(WindowMessage::Refresh);
+                Ok(String::new())
+            }
+        },
+        Command::Unlock(passphrase) => {
+            if let Context::Local = context {
+                return Err("ClipStash is not running".to_string());
+            }
+
+            let mut clip_history = ClipboardHistory::get_instance();
+            if clip_history.is_locked() {
+                clip_history.unlock(passphrase)?;
+                save_history(&clip_history)?;
+            } else {
+                drop(clip_history);
+                let passphrase_hash = Config::get_instance().get_lock_passphrase_hash();
+                let mut window_lock = WindowLock::get_instance();
+                if !window_lock.is_locked() {
+                    return Err("ClipStash isn't locked".to_string());
+                }
+                window_lock.unlock(passphrase, &passphrase_hash)?;
+                if let Context::Window(window) = context {
+                    window.send(WindowMessage::Refresh);
+                }
+            }
+            Ok(String::new())
+        }
+        Command::Quit => match context {
+            Context::Local => Err("ClipStash is not running".to_string()),
+            // The daemon exits once the reply has been sent
+            Context::Daemon => Ok(String::new()),
+            Context::Window(window) => {
+                window.send(WindowMessage::Quit);
+                Ok(String::new())
+            }
+        },
+        Command::List => {
+            let clip_history = ClipboardHistory::get_instance();
+            let output = clip_history
+                .get_items()
+                .iter()
+                .map(|item| format!("{}\t{}\n", item.id, escape_line(item.display_text())))
+                .collect();
+            Ok(output)
+        }
+        Command::Search(query) => {
+            let clip_history = ClipboardHistory::get_instance();
+            let output = clip_history
+                .search(query)
+                .iter()
+                .map(|item| format!("{}\t{}\n", item.id, escape_line(item.display_text())))
+                .collect();
+            Ok(output)
+        }
+        Command::Copy(id) => {
+            // On Linux the clipboard only holds a reference to the process that
+            // copied, which would exit right away. Only a running instance,
+            // which stays around, can copy.
+            #[cfg(target_os = "linux")]
+            if let Context::Local = context {
+                return Err(
+                    "ClipStash is not running, and on Linux what it copies is lost once it exits"
+                        .to_string(),
+                );
+            }
+
+            let mut clip_history = ClipboardHistory::get_instance();
+            let index = find_item(&clip_history, *id)?;
+
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clip_history.get_item(index).copy_to(&mut clipboard)?;
+            clip_history.record_copy(index);
+            save_history(&clip_history)?;
+            Ok(String::new())
+        }
+        Command::Remove(id) => {
+            let mut clip_history = ClipboardHistory::get_instance();
+            let index = find_item(&clip_history, *id)?;
+
+            clip_history.remove_item(index);
+            save_history(&clip_history)?;
+            Ok(String::new())
+        }
+        Command::Pin(id) | Command::Unpin(id) => {
+            let mut clip_history = ClipboardHistory::get_instance();
+            let index = find_item(&clip_history, *id)?;
+
+            clip_history.set_pinned(index, matches!(command, Command::Pin(_)));
+            save_history(&clip_history)?;
+            Ok(String::new())
+        }
+        Command::Tag(id, tag) | Command::Untag(id, tag) => {
+            let mut clip_history = ClipboardHistory::get_instance();
+            let index = find_item(&clip_history, *id)?;
+
+            if let Command::Tag(..) = command {
+                clip_history.add_tag(index, tag);
+            } else {
+                clip_history.remove_tag(index, tag);
+            }
+            save_history(&clip_history)?;
+            Ok(String::new())
+        }
+        Command::Clear => {
+            let mut clip_history = ClipboardHistory::get_instance();
+            clip_history.clear_items();
+            save_history(&clip_history)?;
+            Ok(String::new())
+        }
+        Command::Export {
+            format,
+            filter,
+            output,
+        } => {
+            let clip_history = ClipboardHistory::get_instance();
+            let exported = export::export(&clip_history.get_items(), *format, filter);
+
+            match output {
+                Some(path) => {
+                    std::fs::write(path, exported).map_err(|e| e.to_string())?;
+                    Ok(String::new())
+                }
+                None => Ok(exported),
+            }
+        }
+        Command::Import { source, path } => {
+            let items = import::read(*source, path.as_deref())?;
+            let total = items.len();
+
+            let mut clip_history = ClipboardHistory::get_instance();
+            let imported = clip_history.import_items(items);
+            save_history(&clip_history)?;
+            Ok(format!(
+                "Imported {} items, {} were already in the history\n",
+                imported,
+                total - imported
+            ))
+        }
+    }
+}
+
+fn read_passphrase() -> Result<String, String> {
+    let mut passphrase = String::new();
+    std::io::stdin()
+        .read_line(&mut passphrase)
+        .map_err(|e| e.to_string())?;
+    Ok(passphrase.trim_end_matches(['\n', '\r']).to_string())
+}
+
+// Whether the items can be read or changed, by the command line or D-Bus
+pub fn check_access() -> Result<(), String> {
+    if ClipboardHistory::get_instance().is_locked() {
+        return Err("The history is encrypted, unlock it first".to_string());
+    }
+    if WindowLock::get_instance().is_locked() {
+        return Err("ClipStash is locked, unlock it first".to_string());
+    }
+    Ok(())
+}
+
+pub fn find_item(clip_history: &ClipboardHistory, id: u64) -> Result<usize, String> {
+    clip_history
+        .index_of(id)
+        .ok_or(format!("No item with id {}", id))
+}
+
+pub fn save_history(clip_history: &ClipboardHistory) -> Result<(), String> {
+    if Config::get_instance().get_save_history() {
+        clip_history.save_to_file().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Keeps every item on a single line so the output can be piped to other tools
+pub fn escape_line(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+#[cfg(unix)]
+pub fn unescape_line(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some('t') => text.push('\t'),
+            Some('s') => text.push(' '),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
@@ -0,0 +1,310 @@
+// Tells what a clip is from its text, for the badge in the history table and
+// the actions that only make sense for some clips, like opening a URL
+
+use directories::BaseDirs;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::highlight::{self, Language};
+
+static URL: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?i)(https?://|ftp://|www\.)\S+$").unwrap());
+static EMAIL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?i)(mailto:)?[a-z0-9._%+-]+@[a-z0-9-]+(\.[a-z0-9-]+)*\.[a-z]{2,}$").unwrap()
+});
+static RGB_COLOR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?i)rgba?\(\s*(\d{1,3})\s*,\s*(\d{1,3})\s*,\s*(\d{1,3})\s*(?:,\s*(\d*\.?\d+)\s*)?\)$",
+    )
+    .unwrap()
+});
+// Thousands separators, decimals, exponents and hexadecimal
+static NUMBER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^([+-]?(\d{1,3}(,\d{3})+|\d+)(\.\d+)?([eE][+-]?\d+)?|0[xX][0-9a-fA-F]+)$").unwrap()
+});
+// e.g. +1 555 123 4567, (555) 123-4567 or 555.123.4567
+static PHONE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\+\d{1,3}[\s.-]?)?(\(\d{1,4}\)[\s.-]?)?\d{2,4}([\s.-]\d{2,4}){1,4}$").unwrap()
+});
+// Dates look like phone numbers with few digits
+static DATE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d{1,4}[./-]\d{1,2}[./-]\d{1,4}$").unwrap());
+// Absolute and relative paths on Unix, drive letters and network shares on Windows
+static FILE_PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^((~|\.{1,2})?/[^/]|[a-zA-Z]:[\\/]|\\\\[^\\\s]+\\)").unwrap());
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ContentType {
+    #[default]
+    Text,
+    Url,
+    Email,
+    FilePath,
+    Color,
+    Json,
+    Number,
+    Phone,
+    Code,
+    Image,
+}
+
+impl ContentType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentType::Text => "Text",
+            ContentType::Url => "URL",
+            ContentType::Email => "Email",
+            ContentType::FilePath => "Path",
+            ContentType::Color => "Color",
+            ContentType::Json => "JSON",
+            ContentType::Number => "Number",
+            ContentType::Phone => "Phone",
+            ContentType::Code => "Code",
+            ContentType::Image => "Image",
+        }
+    }
+
+    // The menu item that opens the clip with the default application
+    pub fn open_label(&self) -> Option<&'static str> {
+        match self {
+            ContentType::Url => Some("Open URL"),
+            ContentType::Email => Some("Send Email"),
+            ContentType::FilePath => Some("Open Folder"),
+            _ => None,
+        }
+    }
+
+    // What's opened for the clip: the URL, a mailto: link, or the folder of
+    // the path if it exists
+    pub fn open_target(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+        match self {
+            ContentType::Url if text.to_lowercase().starts_with("www.") => {
+                Some(format!("https://{}", text))
+            }
+            ContentType::Url => Some(text.to_string()),
+            ContentType::Email if text.to_lowercase().starts_with("mailto:") => {
+                Some(text.to_string())
+            }
+            ContentType::Email => Some(format!("mailto:{}", text)),
+            ContentType::FilePath => folder_of(text)
+                .filter(|folder| folder.is_dir())
+                .map(|folder| folder.to_string_lossy().into_owned()),
+            _ => None,
+        }
+    }
+}
+
+// Single lines are checked for the short things people copy, then any text
+// for JSON and code
+pub fn classify(text: &str) -> ContentType {
+    let text = text.trim();
+    if !text.contains('\n') {
+        if URL.is_match(text) {
+            return ContentType::Url;
+        } else if EMAIL.is_match(text) {
+            return ContentType::Email;
+        } else if parse_color(text).is_some() {
+            return ContentType::Color;
+        } else if NUMBER.is_match(text) {
+            return ContentType::Number;
+        } else if is_phone_number(text) {
+            return ContentType::Phone;
+        } else if FILE_PATH.is_match(text) {
+            return ContentType::FilePath;
+        }
+    }
+
+    match highlight::detect(text) {
+        Some(Language::Json) => ContentType::Json,
+        Some(_) => ContentType::Code,
+        None => ContentType::Text,
+    }
+}
+
+fn is_phone_number(text: &str) -> bool {
+    let digits = text.chars().filter(|c| c.is_ascii_digit()).count();
+    (7..=15).contains(&digits) && PHONE.is_match(text) && !DATE.is_match(text)
+}
+
+// #rrggbb, #rrggbbaa, rgb(r, g, b) and rgba(r, g, b, a). The short #rgb and
+// #rgba forms are left out, since #123 is far more often an issue number.
+pub fn parse_color(text: &str) -> Option<[f32; 4]> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('#') {
+        if !matches!(hex.len(), 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channels: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        let channel = |i: usize| channels.get(i).map_or(1.0, |c| *c as f32 / 255.0);
+        return Some([channel(0), channel(1), channel(2), channel(3)]);
+    }
+
+    let captures = RGB_COLOR.captures(text)?;
+    let mut color = [1.0; 4];
+    for (i, channel) in color.iter_mut().take(3).enumerate() {
+        let value: u32 = captures[i + 1].parse().ok()?;
+        if value > 255 {
+            return None;
+        }
+        *channel = value as f32 / 255.0;
+    }
+    if let Some(alpha) = captures.get(4) {
+        color[3] = alpha.as_str().parse::<f32>().ok()?.min(1.0);
+    }
+    Some(color)
+}
+
+// The folder the path is in, or the path itself if it's a folder
+fn folder_of(path: &str) -> Option<PathBuf> {
+    let path = match path.strip_prefix('~') {
+        Some(rest) => BaseDirs::new()?
+            .home_dir()
+            .join(rest.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(path),
+    };
+    if path.is_dir() {
+        Some(path)
+    } else {
+        path.parent().map(Path::to_path_buf)
+    }
+}
+
+// Indents JSON with two spaces. Unlike a round trip through serde_json, the
+// keys keep their order and the numbers are left as they were written.
+pub fn pretty_json(text: &str) -> Option<String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(text).ok()?;
+
+    let mut pretty = String::with_capacity(text.len() * 2);
+    let mut indent = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = text.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            pretty.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                pretty.push(c);
+            }
+            '{' | '[' => {
+                pretty.push(c);
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                // Empty objects and arrays stay on one line
+                match chars.peek() {
+                    Some('}') | Some(']') => pretty.extend(chars.next()),
+                    _ => {
+                        indent += 1;
+                        push_line_break(&mut pretty, indent);
+                    }
+                }
+            }
+            '}' | ']' => {
+                indent -= 1;
+                push_line_break(&mut pretty, indent);
+                pretty.push(c);
+            }
+            ',' => {
+                pretty.push(c);
+                push_line_break(&mut pretty, indent);
+            }
+            ':' => pretty.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => pretty.push(c),
+        }
+    }
+    Some(pretty)
+}
+
+fn push_line_break(text: &mut String, indent: usize) {
+    text.push('\n');
+    text.push_str(&"  ".repeat(indent));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_are_classified() {
+        let cases = [
+            ("https://example.com/a?b=c", ContentType::Url),
+            ("www.example.com", ContentType::Url),
+            ("someone@example.co.uk", ContentType::Email),
+            ("#ff8800", ContentType::Color),
+            ("rgba(255, 136, 0, 0.5)", ContentType::Color),
+            ("#123", ContentType::Text),
+            ("-1,234.5", ContentType::Number),
+            ("0x1F", ContentType::Number),
+            ("+1 555 123 4567", ContentType::Phone),
+            ("(555) 123-4567", ContentType::Phone),
+            ("2024-01-31", ContentType::Text),
+            ("192.168.0.1", ContentType::Text),
+            ("/home/someone/notes.txt", ContentType::FilePath),
+            ("~/Downloads", ContentType::FilePath),
+            ("C:\\Users\\someone", ContentType::FilePath),
+            ("// not a path", ContentType::Text),
+            ("{\"a\": [1, 2]}", ContentType::Json),
+            (
+                "fn main() {\n    let mut x = 1;\n    println!(\"{}\", x);\n}",
+                ContentType::Code,
+            ),
+            ("See you at 5, bring the notes", ContentType::Text),
+        ];
+        for (text, content_type) in cases {
+            assert_eq!(classify(text), content_type, "{}", text);
+        }
+    }
+
+    #[test]
+    fn colors_are_parsed() {
+        assert_eq!(parse_color("#ff8800"), Some([1.0, 136.0 / 255.0, 0.0, 1.0]));
+        assert_eq!(
+            parse_color("#00000080"),
+            Some([0.0, 0.0, 0.0, 128.0 / 255.0])
+        );
+        assert_eq!(parse_color("rgb(0, 255, 0)"), Some([0.0, 1.0, 0.0, 1.0]));
+        assert_eq!(parse_color("rgb(0, 256, 0)"), None);
+        assert_eq!(parse_color("#12345"), None);
+        // Issue references, not colors
+        assert_eq!(parse_color("#123"), None);
+        assert_eq!(parse_color("#1234"), None);
+    }
+
+    #[test]
+    fn urls_and_emails_are_opened_as_links() {
+        let open_target = |text: &str| classify(text).open_target(text);
+        assert_eq!(
+            open_target("www.example.com"),
+            Some("https://www.example.com".to_string())
+        );
+        assert_eq!(
+            open_target("someone@example.com"),
+            Some("mailto:someone@example.com".to_string())
+        );
+        assert_eq!(open_target("/no/such/folder/file.txt"), None);
+    }
+
+    #[test]
+    fn json_is_pretty_printed_in_order() {
+        let json = r#"{"b": 1.50, "a": [], "c": {"d": "x, \"y\": z"}, "e": [true, null]}"#;
+        let pretty = "{\n  \"b\": 1.50,\n  \"a\": [],\n  \"c\": {\n    \"d\": \"x, \\\"y\\\": z\"\n  },\n  \"e\": [\n    true,\n    null\n  ]\n}";
+        assert_eq!(pretty_json(json).as_deref(), Some(pretty));
+        assert_eq!(pretty_json("{\"a\": }"), None);
+    }
+}
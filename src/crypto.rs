@@ -0,0 +1,149 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+const SALT_LENGTH: usize = 16;
+
+// How encrypted data is stored on disk, with the binary fields in base64
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedData {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// A key derived from the user's passphrase, along with the salt used to derive it
+pub struct EncryptionKey {
+    key: [u8; 32],
+    salt: [u8; SALT_LENGTH],
+}
+
+impl EncryptionKey {
+    // Derives a key from the passphrase with a new random salt
+    pub fn new(passphrase: &str) -> Result<Self, String> {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt)
+    }
+
+    // Derives the key the data was encrypted with, using its salt
+    pub fn for_data(passphrase: &str, data: &EncryptedData) -> Result<Self, String> {
+        let salt = BASE64
+            .decode(&data.salt)
+            .ok()
+            .and_then(|salt| salt.try_into().ok())
+            .ok_or("Invalid salt in encrypted data")?;
+        Self::derive(passphrase, salt)
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LENGTH]) -> Result<Self, String> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        Ok(Self { key, salt })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> EncryptedData {
+        let cipher = XChaCha20Poly1305::new(&self.key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .expect("Failed to encrypt data");
+
+        EncryptedData {
+            version: 1,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        }
+    }
+
+    // Fails if the passphrase was wrong or the data was tampered with
+    pub fn decrypt(&self, data: &EncryptedData) -> Result<Vec<u8>, String> {
+        let nonce = BASE64
+            .decode(&data.nonce)
+            .ok()
+            .filter(|nonce| nonce.len() == 24)
+            .ok_or("Invalid nonce in encrypted data")?;
+        let ciphertext = BASE64
+            .decode(&data.ciphertext)
+            .map_err(|_| "Invalid encrypted data")?;
+
+        let cipher = XChaCha20Poly1305::new(&self.key.into());
+        cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| "Wrong passphrase".to_string())
+    }
+}
+
+// Hashes a passphrase to be stored, e.g. in the preferences, as a PHC string
+pub fn hash_passphrase(passphrase: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(passphrase.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash passphrase: {}", e))
+}
+
+pub fn verify_passphrase(passphrase: &str, passphrase_hash: &str) -> bool {
+    PasswordHash::new(passphrase_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(passphrase.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_decrypts_with_its_passphrase_only() {
+        let key = EncryptionKey::new("passphrase").unwrap();
+        let data = key.encrypt(b"plaintext");
+        assert_ne!(BASE64.decode(&data.ciphertext).unwrap(), b"plaintext");
+
+        let key = EncryptionKey::for_data("passphrase", &data).unwrap();
+        assert_eq!(key.decrypt(&data).unwrap(), b"plaintext");
+        let wrong_key = EncryptionKey::for_data("wrong passphrase", &data).unwrap();
+        assert_eq!(
+            wrong_key.decrypt(&data),
+            Err("Wrong passphrase".to_string())
+        );
+    }
+
+    #[test]
+    fn tampered_data_fails_to_decrypt() {
+        let key = EncryptionKey::new("passphrase").unwrap();
+        let mut data = key.encrypt(b"plaintext");
+        let mut ciphertext = BASE64.decode(&data.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        data.ciphertext = BASE64.encode(ciphertext);
+        assert!(key.decrypt(&data).is_err());
+
+        data.nonce = BASE64.encode([0u8; 12]);
+        assert!(key.decrypt(&data).is_err());
+    }
+
+    #[test]
+    fn passphrase_hashes_verify() {
+        let hash = hash_passphrase("passphrase").unwrap();
+        assert!(verify_passphrase("passphrase", &hash));
+        assert!(!verify_passphrase("wrong passphrase", &hash));
+        assert!(!verify_passphrase("passphrase", "not a hash"));
+    }
+}
@@ -0,0 +1,210 @@
+use arboard::Clipboard;
+use once_cell::sync::OnceCell;
+use zbus::blocking::{connection, Connection};
+use zbus::fdo;
+use zbus::{interface, SignalContext};
+
+use crate::cli;
+use crate::history::{ClipboardHistory, ClipboardItem};
+
+const BUS_NAME: &str = "io.github.clipstash.ClipStash";
+const OBJECT_PATH: &str = "/io/github/clipstash/ClipStash";
+
+static CONNECTION: OnceCell<Connection> = OnceCell::new();
+
+// Items are sent over D-Bus as (id, text, pinned)
+type DBusItem = (u64, String, bool);
+
+fn to_dbus_item(item: &ClipboardItem) -> DBusItem {
+    (item.id, item.display_text().to_string(), item.pinned)
+}
+
+struct ClipStashService;
+
+#[interface(name = "io.github.clipstash.ClipStash1")]
+impl ClipStashService {
+    fn list(&self) -> fdo::Result<Vec<DBusItem>> {
+        cli::check_access().map_err(fdo::Error::AccessDenied)?;
+        let clip_history = ClipboardHistory::get_instance();
+        Ok(clip_history.get_items().iter().map(to_dbus_item).collect())
+    }
+
+    fn search(&self, query: &str) -> fdo::Result<Vec<DBusItem>> {
+        cli::check_access().map_err(fdo::Error::AccessDenied)?;
+        let clip_history = ClipboardHistory::get_instance();
+        Ok(clip_history
+            .search(query)
+            .iter()
+            .map(to_dbus_item)
+            .collect())
+    }
+
+    fn copy(&self, id: u64) -> fdo::Result<()> {
+        cli::check_access().map_err(fdo::Error::AccessDenied)?;
+        let mut clip_history = ClipboardHistory::get_instance();
+        let index = cli::find_item(&clip_history, id).map_err(fdo::Error::InvalidArgs)?;
+
+        let mut clipboard = Clipboard::new().map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        clip_history
+            .get_item(index)
+            .copy_to(&mut clipboard)
+            .map_err(fdo::Error::Failed)?;
+        clip_history.record_copy(index);
+        cli::save_history(&clip_history).map_err(fdo::Error::IOError)
+    }
+
+    fn pin(&self, id: u64, pinned: bool) -> fdo::Result<()> {
+        cli::check_access().map_err(fdo::Error::AccessDenied)?;
+        let mut clip_history = ClipboardHistory::get_instance();
+        let index = cli::find_item(&clip_history, id).map_err(fdo::Error::InvalidArgs)?;
+
+        clip_history.set_pinned(index, pinned);
+        cli::save_history(&clip_history).map_err(fdo::Error::IOError)
+    }
+
+    fn delete(&self, id: u64) -> fdo::Result<()> {
+        cli::check_access().map_err(fdo::Error::AccessDenied)?;
+        let mut clip_history = ClipboardHistory::get_instance();
+        let index = cli::find_item(&clip_history, id).map_err(fdo::Error::InvalidArgs)?;
+
+        clip_history.remove_item(index);
+        cli::save_history(&clip_history).map_err(fdo::Error::IOError)
+    }
+
+    #[zbus(signal)]
+    async fn new_clip(ctxt: &SignalContext<'_>, id: u64, text: &str) -> zbus::Result<()>;
+}
+
+// Registers the service on the session bus. Set DBUS_SESSION_BUS_ADDRESS
+// to run it against a private bus, e.g. one started with `dbus-daemon --session`.
+pub fn start() -> zbus::Result<()> {
+    let connection = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, ClipStashService)?
+        .build()?;
+
+    let _ = CONNECTION.set(connection);
+    Ok(())
+}
+
+// Emits the NewClip signal, does nothing if the service isn't running
+pub fn notify_new_clip(id: u64, text: &str) {
+    let Some(connection) = CONNECTION.get() else {
+        return;
+    };
+
+    let result = SignalContext::new(connection.inner(), OBJECT_PATH)
+        .and_then(|ctxt| zbus::block_on(ClipStashService::new_clip(&ctxt, id, text)));
+    if let Err(e) = result {
+        eprintln!("Failed to emit NewClip signal: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use zbus::blocking::Proxy;
+
+    const INTERFACE: &str = "io.github.clipstash.ClipStash1";
+
+    // A private session bus, stopped when the test ends
+    struct PrivateBus(Child);
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_private_bus() -> PrivateBus {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--print-address", "--nofork"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon isn't installed");
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address.trim());
+        PrivateBus(child)
+    }
+
+    fn error_name(error: zbus::Error) -> String {
+        match error {
+            zbus::Error::MethodError(name, _, _) => name.to_string(),
+            e => panic!("Not a method error: {}", e),
+        }
+    }
+
+    // Uses the history of a temporary data dir, so run it on its own:
+    // `cargo test -- --ignored dbus`
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn dbus_service_lists_searches_pins_and_deletes() {
+        let dir = std::env::temp_dir().join(format!("clipstash-dbus-{}", std::process::id()));
+        std::env::set_var("XDG_DATA_HOME", dir.join("data"));
+        std::env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        let _bus = start_private_bus();
+
+        let (first_id, second_id) = {
+            let mut clip_history = ClipboardHistory::get_instance();
+            let first_id = clip_history.add_item(ClipboardItem::new(0, "first clip".to_string()));
+            let second_id = clip_history.add_item(ClipboardItem::new(0, "second clip".to_string()));
+            (first_id.unwrap(), second_id.unwrap())
+        };
+        start().unwrap();
+
+        let connection = Connection::session().unwrap();
+        let proxy = Proxy::new(&connection, BUS_NAME, OBJECT_PATH, INTERFACE).unwrap();
+        let list = || proxy.call::<_, _, Vec<DBusItem>>("List", &()).unwrap();
+
+        assert_eq!(
+            list(),
+            [
+                (first_id, "first clip".to_string(), false),
+                (second_id, "second clip".to_string(), false),
+            ]
+        );
+        let found: Vec<DBusItem> = proxy.call("Search", &("second",)).unwrap();
+        assert_eq!(found, [(second_id, "second clip".to_string(), false)]);
+
+        proxy.call::<_, _, ()>("Pin", &(first_id, true)).unwrap();
+        assert!(list()[0].2);
+
+        // Copying needs an X server, an unknown id fails before that
+        let error = proxy.call::<_, _, ()>("Copy", &(999u64,)).unwrap_err();
+        assert_eq!(error_name(error), "org.freedesktop.DBus.Error.InvalidArgs");
+        if std::env::var_os("DISPLAY").is_some() {
+            proxy.call::<_, _, ()>("Copy", &(second_id,)).unwrap();
+            let clip_history = ClipboardHistory::get_instance();
+            assert_eq!(clip_history.items()[1].copy_count, 1);
+        }
+
+        proxy.call::<_, _, ()>("Delete", &(second_id,)).unwrap();
+        assert_eq!(list().len(), 1);
+
+        // The signal is received on a thread of its own, since it blocks
+        let (tx, rx) = mpsc::channel();
+        let signals = proxy.receive_signal("NewClip").unwrap();
+        std::thread::spawn(move || {
+            for signal in signals {
+                tx.send(signal.body().deserialize::<(u64, String)>().unwrap())
+                    .unwrap();
+            }
+        });
+        notify_new_clip(42, "new clip");
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            (42, "new clip".to_string())
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
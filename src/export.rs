@@ -0,0 +1,316 @@
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::collections::HashSet;
+
+use crate::history::ClipboardItem;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    JsonLines,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            "csv" => Ok(ExportFormat::Csv),
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            _ => Err(format!("Unknown export format: {}", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+        }
+    }
+}
+
+// Items are exported only if they match all of the set filters
+#[derive(Clone, Default)]
+pub struct ExportFilter {
+    // Unix timestamps, `since` is inclusive and `until` exclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub tag: Option<String>,
+    pub pinned_only: bool,
+    // Ids of the items to export, e.g. the ones selected in the window
+    pub ids: Option<HashSet<u64>>,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, item: &ClipboardItem) -> bool {
+        // Sensitive items are never written to disk
+        if item.sensitive {
+            return false;
+        }
+        if self.since.is_some_and(|since| item.created_at < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| item.created_at >= until) {
+            return false;
+        }
+        if self.ids.as_ref().is_some_and(|ids| !ids.contains(&item.id)) {
+            return false;
+        }
+        if let Some(tag) = &self.tag {
+            if !item.tags.contains(tag) {
+                return false;
+            }
+        }
+        !self.pinned_only || item.pinned
+    }
+}
+
+// Accepts a date (start of the day in local time) or an RFC 3339 date & time
+pub fn parse_date(date: &str) -> Result<i64, String> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(date) {
+        return Ok(date_time.timestamp());
+    }
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {} (expected YYYY-MM-DD)", date))?;
+    let date_time = Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .ok_or(format!("Invalid date: {}", date))?;
+    Ok(date_time.timestamp())
+}
+
+pub fn format_date(timestamp: i64) -> String {
+    if timestamp == 0 {
+        return String::new();
+    }
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(date_time) => date_time.to_rfc3339(),
+        None => String::new(),
+    }
+}
+
+pub fn export(items: &[ClipboardItem], format: ExportFormat, filter: &ExportFilter) -> String {
+    let items: Vec<&ClipboardItem> = items.iter().filter(|item| filter.matches(item)).collect();
+
+    match format {
+        ExportFormat::JsonLines => to_json_lines(&items),
+        ExportFormat::Csv => to_csv(&items),
+        ExportFormat::Markdown => to_markdown(&items),
+    }
+}
+
+// One JSON object per line, with the same fields as the history file
+fn to_json_lines(items: &[&ClipboardItem]) -> String {
+    items
+        .iter()
+        .map(|item| format!("{}\n", serde_json::to_string(item).unwrap()))
+        .collect()
+}
+
+fn to_csv(items: &[&ClipboardItem]) -> String {
+    let mut csv = String::from("id,created_at,pinned,tags,text\r\n");
+    for item in items {
+        let fields = [
+            item.id.to_string(),
+            format_date(item.created_at),
+            item.pinned.to_string(),
+            item.tags.join(";"),
+            item.text.clone(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+// Quotes fields as described in RFC 4180
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn to_markdown(items: &[&ClipboardItem]) -> String {
+    let mut markdown = String::from("# ClipStash History\n");
+    for item in items {
+        markdown.push_str(&format!("\n## Item {}\n\n", item.id));
+
+        let created_at = format_date(item.created_at);
+        if !created_at.is_empty() {
+            markdown.push_str(&format!("- Copied: {}\n", created_at));
+        }
+        if item.pinned {
+            markdown.push_str("- Pinned\n");
+        }
+        if !item.tags.is_empty() {
+            markdown.push_str(&format!("- Tags: {}\n", item.tags.join(", ")));
+        }
+
+        // The fence has to be longer than any run of backticks in the text
+        let longest_backticks = item
+            .text
+            .split(|c| c != '`')
+            .map(|backticks| backticks.len())
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest_backticks.max(2) + 1);
+        markdown.push_str(&format!("\n{}\n{}\n{}\n", fence, item.text, fence));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import;
+
+    fn test_items() -> Vec<ClipboardItem> {
+        let mut items = vec![
+            ClipboardItem::new(1, "plain text".to_string()),
+            ClipboardItem::new(2, "say \"hi\", then\r\nleave".to_string()),
+            ClipboardItem::new(3, "```rust\nfn main() {}\n```".to_string()),
+            ClipboardItem::new(4, "hunter2".to_string()),
+        ];
+        for (i, item) in items.iter_mut().enumerate() {
+            item.created_at = 1_700_000_000 + i as i64 * 86_400;
+        }
+        items[1].pinned = true;
+        items[1].tags = vec!["work".to_string(), "notes".to_string()];
+        items[3].sensitive = true;
+        items
+    }
+
+    // Imported items get new ids, so they're told apart by their text
+    fn exported_ids(filter: &ExportFilter) -> Vec<u64> {
+        let items = test_items();
+        let jsonl = export(&items, ExportFormat::JsonLines, filter);
+        import::read_clipstash(&jsonl)
+            .unwrap()
+            .iter()
+            .filter_map(|imported| items.iter().find(|item| item.text == imported.text))
+            .map(|item| item.id)
+            .collect()
+    }
+
+    // Splits RFC 4180 rows into fields
+    fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut chars = csv.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if in_quotes && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = !in_quotes,
+                ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+                '\r' if !in_quotes && chars.peek() == Some(&'\n') => {
+                    chars.next();
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                c => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn json_lines_are_read_back() {
+        let items = test_items();
+        let jsonl = export(&items, ExportFormat::JsonLines, &ExportFilter::default());
+        let imported = import::read_clipstash(&jsonl).unwrap();
+
+        // The sensitive item is left out
+        assert_eq!(imported.len(), 3);
+        for (item, imported) in items.iter().zip(&imported) {
+            assert_eq!(imported.text, item.text);
+            assert_eq!(imported.created_at, item.created_at);
+            assert_eq!(imported.pinned, item.pinned);
+            assert_eq!(imported.tags, item.tags);
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let items = test_items();
+        let csv = export(&items, ExportFormat::Csv, &ExportFilter::default());
+        let rows = parse_csv(&csv);
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], ["id", "created_at", "pinned", "tags", "text"]);
+        for (item, row) in items.iter().zip(&rows[1..]) {
+            assert_eq!(row[0], item.id.to_string());
+            assert_eq!(parse_date(&row[1]), Ok(item.created_at));
+            assert_eq!(row[2], item.pinned.to_string());
+            assert_eq!(row[3], item.tags.join(";"));
+            assert_eq!(row[4], item.text);
+        }
+    }
+
+    #[test]
+    fn markdown_fences_are_longer_than_the_text_backticks() {
+        let filter = ExportFilter {
+            ids: Some(HashSet::from([2, 3])),
+            ..Default::default()
+        };
+        let markdown = export(&test_items(), ExportFormat::Markdown, &filter);
+        let expected = format!(
+            "# ClipStash History\n\n\
+             ## Item 2\n\n\
+             - Copied: {}\n\
+             - Pinned\n\
+             - Tags: work, notes\n\n\
+             ```\nsay \"hi\", then\r\nleave\n```\n\n\
+             ## Item 3\n\n\
+             - Copied: {}\n\n\
+             ````\n```rust\nfn main() {{}}\n```\n````\n",
+            format_date(1_700_086_400),
+            format_date(1_700_172_800)
+        );
+        assert_eq!(markdown, expected);
+    }
+
+    #[test]
+    fn items_are_filtered() {
+        assert_eq!(exported_ids(&ExportFilter::default()), [1, 2, 3]);
+
+        let date_range = ExportFilter {
+            since: Some(1_700_086_400),
+            until: Some(1_700_172_800),
+            ..Default::default()
+        };
+        assert_eq!(exported_ids(&date_range), [2]);
+
+        let tag = ExportFilter {
+            tag: Some("notes".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(exported_ids(&tag), [2]);
+        let unknown_tag = ExportFilter {
+            tag: Some("note".to_string()),
+            ..Default::default()
+        };
+        assert!(exported_ids(&unknown_tag).is_empty());
+
+        let pinned = ExportFilter {
+            pinned_only: true,
+            ..Default::default()
+        };
+        assert_eq!(exported_ids(&pinned), [2]);
+
+        // The sensitive item isn't exported even when it's selected
+        let ids = ExportFilter {
+            ids: Some(HashSet::from([3, 4])),
+            ..Default::default()
+        };
+        assert_eq!(exported_ids(&ids), [3]);
+    }
+}
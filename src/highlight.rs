@@ -0,0 +1,726 @@
+// Detects the language of code clips and splits their lines into tokens,
+// which the preview draws in the colors of the current skin
+
+use std::ops::Range;
+use std::sync::Mutex;
+
+// Only the start of long clips is looked at to tell their language
+const DETECT_SAMPLE: usize = 16 * 1024;
+
+// How many signals of a language a clip needs to be highlighted as it
+const MIN_SCORE: u32 = 3;
+
+// The colors of the tokens, plain text keeps the skin's text color
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub keyword: [f32; 4],
+    pub string: [f32; 4],
+    pub number: [f32; 4],
+    pub comment: [f32; 4],
+    // Keys of JSON objects
+    pub key: [f32; 4],
+}
+
+impl Theme {
+    pub fn color(&self, kind: TokenKind) -> Option<[f32; 4]> {
+        match kind {
+            TokenKind::Plain => None,
+            TokenKind::Keyword => Some(self.keyword),
+            TokenKind::String => Some(self.string),
+            TokenKind::Number => Some(self.number),
+            TokenKind::Comment => Some(self.comment),
+            TokenKind::Key => Some(self.key),
+        }
+    }
+}
+
+// Set along with the rest of the skin
+static THEME: Mutex<Theme> = Mutex::new(Theme {
+    keyword: [0.4, 0.6, 1.0, 1.0],
+    string: [0.6, 0.85, 0.55, 1.0],
+    number: [0.95, 0.7, 0.4, 1.0],
+    comment: [0.5, 0.5, 0.5, 1.0],
+    key: [0.75, 0.6, 1.0, 1.0],
+});
+
+pub fn set_theme(theme: Theme) {
+    *THEME.lock().unwrap() = theme;
+}
+
+pub fn theme() -> Theme {
+    *THEME.lock().unwrap()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    C,
+    Go,
+    Java,
+    JavaScript,
+    Json,
+    Python,
+    Rust,
+    Shell,
+    Sql,
+}
+
+impl Language {
+    pub const ALL: [Language; 9] = [
+        Language::C,
+        Language::Go,
+        Language::Java,
+        Language::JavaScript,
+        Language::Json,
+        Language::Python,
+        Language::Rust,
+        Language::Shell,
+        Language::Sql,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::C => "C/C++",
+            Language::Go => "Go",
+            Language::Java => "Java",
+            Language::JavaScript => "JavaScript",
+            Language::Json => "JSON",
+            Language::Python => "Python",
+            Language::Rust => "Rust",
+            Language::Shell => "Shell",
+            Language::Sql => "SQL",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Language> {
+        match extension.to_lowercase().as_str() {
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" => Some(Language::C),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(Language::JavaScript),
+            "json" => Some(Language::Json),
+            "py" => Some(Language::Python),
+            "rs" => Some(Language::Rust),
+            "sh" | "bash" | "zsh" => Some(Language::Shell),
+            "sql" => Some(Language::Sql),
+            _ => None,
+        }
+    }
+
+    fn syntax(&self) -> Syntax {
+        match self {
+            Language::C => Syntax {
+                keywords: C_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["//"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("\"", false), ("'", false)],
+                signals: &[
+                    ("#include", 3),
+                    ("#define ", 3),
+                    ("int main(", 3),
+                    ("printf(", 2),
+                    ("std::", 2),
+                    ("sizeof(", 2),
+                    ("NULL", 2),
+                    ("->", 1),
+                    ("void ", 1),
+                ],
+            },
+            Language::Go => Syntax {
+                keywords: GO_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["//"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("`", true), ("\"", false), ("'", false)],
+                signals: &[
+                    ("package ", 2),
+                    ("func ", 2),
+                    (":= ", 2),
+                    ("fmt.", 3),
+                    ("err != nil", 3),
+                    ("import (", 2),
+                ],
+            },
+            Language::Java => Syntax {
+                keywords: JAVA_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["//"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("\"\"\"", true), ("\"", false), ("'", false)],
+                signals: &[
+                    ("public class ", 3),
+                    ("public static void", 3),
+                    ("System.out.", 3),
+                    ("import java.", 3),
+                    ("@Override", 3),
+                    ("private ", 1),
+                    ("void ", 1),
+                    ("new ", 1),
+                ],
+            },
+            Language::JavaScript => Syntax {
+                keywords: JAVASCRIPT_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["//"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("`", true), ("\"", false), ("'", false)],
+                signals: &[
+                    ("function ", 2),
+                    ("=> ", 2),
+                    ("===", 3),
+                    ("console.log", 3),
+                    ("document.", 2),
+                    ("require(", 2),
+                    ("undefined", 2),
+                    ("const ", 1),
+                    ("let ", 1),
+                    ("export ", 1),
+                ],
+            },
+            Language::Json => Syntax {
+                keywords: JSON_KEYWORDS,
+                ignore_case: false,
+                line_comments: &[],
+                block_comment: None,
+                strings: &[("\"", false)],
+                // Only parsing tells JSON apart
+                signals: &[],
+            },
+            Language::Python => Syntax {
+                keywords: PYTHON_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["#"],
+                block_comment: None,
+                strings: &[("\"\"\"", true), ("'''", true), ("\"", false), ("'", false)],
+                signals: &[
+                    ("def ", 2),
+                    ("elif ", 3),
+                    ("__init__", 3),
+                    ("):\n", 2),
+                    ("self.", 1),
+                    ("import ", 1),
+                    ("print(", 1),
+                    ("None", 1),
+                ],
+            },
+            Language::Rust => Syntax {
+                keywords: RUST_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["//"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("\"", true)],
+                signals: &[
+                    ("fn ", 2),
+                    ("let mut ", 3),
+                    ("pub fn ", 3),
+                    ("&self", 3),
+                    ("println!", 3),
+                    ("use std::", 3),
+                    ("#[derive", 3),
+                    ("impl ", 2),
+                    ("unwrap()", 2),
+                    ("Some(", 2),
+                    ("::", 1),
+                    ("-> ", 1),
+                ],
+            },
+            Language::Shell => Syntax {
+                keywords: SHELL_KEYWORDS,
+                ignore_case: false,
+                line_comments: &["#"],
+                block_comment: None,
+                strings: &[("\"", true), ("'", true)],
+                signals: &[
+                    ("; then", 3),
+                    ("esac", 3),
+                    ("fi\n", 2),
+                    ("echo ", 2),
+                    ("sudo ", 2),
+                    ("$(", 2),
+                    ("| grep", 2),
+                    ("${", 1),
+                    ("export ", 1),
+                ],
+            },
+            Language::Sql => Syntax {
+                keywords: SQL_KEYWORDS,
+                ignore_case: true,
+                line_comments: &["--"],
+                block_comment: Some(("/*", "*/")),
+                strings: &[("'", true), ("\"", false)],
+                // Matched against the lowercase clip
+                signals: &[
+                    ("select ", 2),
+                    ("insert into ", 3),
+                    ("create table ", 3),
+                    ("delete from ", 3),
+                    ("group by ", 3),
+                    ("order by ", 2),
+                    (" from ", 1),
+                    ("where ", 1),
+                    ("join ", 1),
+                    (" set ", 1),
+                ],
+            },
+        }
+    }
+}
+
+// Kept in rows rather than one keyword per line
+#[rustfmt::skip]
+const C_KEYWORDS: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "class", "const", "continue", "default", "define",
+    "delete", "do", "double", "else", "endif", "enum", "extern", "false", "float", "for", "if",
+    "ifdef", "ifndef", "include", "inline", "int", "long", "namespace", "new", "nullptr", "private",
+    "protected", "public", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+    "template", "this", "true", "typedef", "union", "unsigned", "using", "virtual", "void",
+    "volatile", "while",
+];
+
+#[rustfmt::skip]
+const GO_KEYWORDS: &[&str] = &[
+    "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough",
+    "false", "for", "func", "go", "goto", "if", "import", "interface", "map", "nil", "package",
+    "range", "return", "select", "struct", "switch", "true", "type", "var",
+];
+
+#[rustfmt::skip]
+const JAVA_KEYWORDS: &[&str] = &[
+    "abstract", "boolean", "break", "byte", "case", "catch", "char", "class", "continue", "default",
+    "do", "double", "else", "enum", "extends", "false", "final", "finally", "float", "for", "if",
+    "implements", "import", "instanceof", "int", "interface", "long", "new", "null", "package",
+    "private", "protected", "public", "return", "short", "static", "super", "switch", "this",
+    "throw", "throws", "true", "try", "var", "void", "while",
+];
+
+#[rustfmt::skip]
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete",
+    "do", "else", "export", "extends", "false", "finally", "for", "from", "function", "if",
+    "import", "in", "instanceof", "let", "new", "null", "of", "return", "static", "super", "switch",
+    "this", "throw", "true", "try", "typeof", "undefined", "var", "void", "while", "yield",
+];
+
+#[rustfmt::skip]
+const JSON_KEYWORDS: &[&str] = &[
+    "false", "null", "true",
+];
+
+#[rustfmt::skip]
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "self", "True", "try",
+    "while", "with", "yield",
+];
+
+#[rustfmt::skip]
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+    "use", "where", "while",
+];
+
+#[rustfmt::skip]
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for", "function",
+    "if", "in", "local", "read", "return", "set", "source", "then", "unset", "until", "while",
+];
+
+#[rustfmt::skip]
+const SQL_KEYWORDS: &[&str] = &[
+    "add", "all", "alter", "and", "as", "asc", "between", "by", "case", "create", "default",
+    "delete", "desc", "distinct", "drop", "else", "end", "exists", "foreign", "from", "full",
+    "group", "having", "in", "index", "inner", "insert", "into", "is", "join", "key", "left",
+    "like", "limit", "not", "null", "offset", "on", "or", "order", "outer", "primary", "references",
+    "right", "select", "set", "table", "then", "union", "unique", "update", "values", "when",
+    "where", "with",
+];
+
+struct Syntax {
+    keywords: &'static [&'static str],
+    // Whether keywords are matched in any case, as in SQL
+    ignore_case: bool,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    // The delimiters of strings, and whether the string can go on to the next line
+    strings: &'static [(&'static str, bool)],
+    // Bits of text that give the language away, and how much they count
+    signals: &'static [(&'static str, u32)],
+}
+
+impl Syntax {
+    fn is_keyword(&self, word: &str) -> bool {
+        if self.ignore_case {
+            self.keywords
+                .iter()
+                .any(|keyword| keyword.eq_ignore_ascii_case(word))
+        } else {
+            self.keywords.contains(&word)
+        }
+    }
+
+    fn starts_comment(&self, text: &str) -> bool {
+        self.line_comments
+            .iter()
+            .chain(self.block_comment.as_ref().map(|(opening, _)| opening))
+            .any(|opening| text.starts_with(opening))
+    }
+}
+
+// Tells the language from a shebang or a file name in a comment on the first
+// line, then from JSON parsing, then from the signals in the text. Prose and
+// code that isn't clearly one language aren't highlighted.
+pub fn detect(text: &str) -> Option<Language> {
+    let first_line = text.lines().next().unwrap_or_default().trim();
+    if let Some(language) = shebang_language(first_line).or_else(|| file_name_hint(first_line)) {
+        return Some(language);
+    }
+
+    let trimmed = text.trim();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return Some(Language::Json);
+    }
+
+    let mut sample_end = text.len().min(DETECT_SAMPLE);
+    while !text.is_char_boundary(sample_end) {
+        sample_end -= 1;
+    }
+    let sample = &text[..sample_end];
+    if !looks_like_code(sample) {
+        return None;
+    }
+    let lowercase_sample = sample.to_lowercase();
+    Language::ALL
+        .iter()
+        .filter_map(|language| {
+            let syntax = language.syntax();
+            let haystack = match syntax.ignore_case {
+                true => lowercase_sample.as_str(),
+                false => sample,
+            };
+            let score: u32 = syntax
+                .signals
+                .iter()
+                .filter(|(signal, _)| haystack.contains(signal))
+                .map(|(_, weight)| weight)
+                .sum();
+            (score >= MIN_SCORE).then_some((score, *language))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, language)| language)
+}
+
+fn shebang_language(first_line: &str) -> Option<Language> {
+    let interpreter = first_line.strip_prefix("#!")?;
+    if interpreter.contains("python") {
+        Some(Language::Python)
+    } else if interpreter.contains("node") {
+        Some(Language::JavaScript)
+    } else if ["/sh", " sh", "bash", "zsh", "ksh", "dash"]
+        .iter()
+        .any(|shell| interpreter.contains(shell))
+    {
+        Some(Language::Shell)
+    } else {
+        None
+    }
+}
+
+// e.g. "// src/main.rs" or "-- report.sql", the file name only counts in a
+// comment of its own language so prose mentioning node.js isn't JavaScript
+fn file_name_hint(first_line: &str) -> Option<Language> {
+    first_line
+        .split(|c: char| c.is_whitespace() || "\"'`()<>,;:".contains(c))
+        .filter_map(|word| word.rsplit_once('.'))
+        .filter(|(name, _)| !name.is_empty())
+        .filter_map(|(_, extension)| Language::from_extension(extension))
+        .find(|language| language.syntax().starts_comment(first_line))
+}
+
+// Prose has few of the symbols code is full of
+fn looks_like_code(sample: &str) -> bool {
+    let symbols = sample
+        .chars()
+        .filter(|c| "{}()[];=<>$*:".contains(*c))
+        .count();
+    let visible = sample.chars().filter(|c| !c.is_whitespace()).count();
+    visible > 0 && symbols * 50 >= visible
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Key,
+}
+
+// A run of a line drawn in one color, as a byte range of the line
+#[derive(Clone, PartialEq, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: Range<usize>,
+}
+
+// A clip's language and its lines split into tokens, the lines are empty
+// when the language isn't known
+pub struct Highlight {
+    pub language: Option<Language>,
+    pub lines: Vec<Vec<Token>>,
+}
+
+impl Highlight {
+    pub fn new(text: &str) -> Self {
+        let language = detect(text);
+        let lines = language
+            .map(|language| tokenize(text, language))
+            .unwrap_or_default();
+        Self { language, lines }
+    }
+}
+
+// One list of tokens for each of `text.lines()`
+pub fn tokenize(text: &str, language: Language) -> Vec<Vec<Token>> {
+    let syntax = language.syntax();
+    // The comment or string that goes on to the next line, and what closes it
+    let mut open = None;
+    text.lines()
+        .map(|line| tokenize_line(line, &syntax, language, &mut open))
+        .collect()
+}
+
+fn tokenize_line(
+    line: &str,
+    syntax: &Syntax,
+    language: Language,
+    open: &mut Option<(TokenKind, &'static str)>,
+) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut start = 0;
+    while start < line.len() {
+        let rest = &line[start..];
+        let (mut kind, length) = if let Some((kind, closing)) = *open {
+            match find_closing(rest, closing, kind == TokenKind::String) {
+                Some(end) => {
+                    *open = None;
+                    (kind, end)
+                }
+                None => (kind, rest.len()),
+            }
+        } else if syntax
+            .line_comments
+            .iter()
+            .any(|opening| rest.starts_with(opening))
+        {
+            (TokenKind::Comment, rest.len())
+        } else if let Some((opening, closing)) = syntax
+            .block_comment
+            .filter(|(opening, _)| rest.starts_with(opening))
+        {
+            match find_closing(&rest[opening.len()..], closing, false) {
+                Some(end) => (TokenKind::Comment, opening.len() + end),
+                None => {
+                    *open = Some((TokenKind::Comment, closing));
+                    (TokenKind::Comment, rest.len())
+                }
+            }
+        } else if let Some((delimiter, multiline)) = syntax
+            .strings
+            .iter()
+            .find(|(delimiter, _)| rest.starts_with(delimiter))
+        {
+            match find_closing(&rest[delimiter.len()..], delimiter, true) {
+                Some(end) => (TokenKind::String, delimiter.len() + end),
+                None => {
+                    if *multiline {
+                        *open = Some((TokenKind::String, delimiter));
+                    }
+                    (TokenKind::String, rest.len())
+                }
+            }
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            if c.is_ascii_digit() {
+                (TokenKind::Number, word_length(rest))
+            } else if is_word_char(c) {
+                let word = &rest[..word_length(rest)];
+                match syntax.is_keyword(word) {
+                    true => (TokenKind::Keyword, word.len()),
+                    false => (TokenKind::Plain, word.len()),
+                }
+            } else {
+                (TokenKind::Plain, c.len_utf8())
+            }
+        };
+
+        let end = start + length;
+        if language == Language::Json
+            && kind == TokenKind::String
+            && line[end..].trim_start().starts_with(':')
+        {
+            kind = TokenKind::Key;
+        }
+        match tokens.last_mut() {
+            Some(last) if last.kind == kind => last.range.end = end,
+            _ => tokens.push(Token {
+                kind,
+                range: start..end,
+            }),
+        }
+        start = end;
+    }
+    tokens
+}
+
+// Where the text just after the closing delimiter starts, skipping the
+// delimiters escaped with a backslash in strings
+fn find_closing(text: &str, closing: &str, escapes: bool) -> Option<usize> {
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        if escapes && c == '\\' {
+            chars.next();
+        } else if text[index..].starts_with(closing) {
+            return Some(index + closing.len());
+        }
+    }
+    None
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Numbers take the same characters as words, for 0xff, 1.5e3 and 10u8
+fn word_length(text: &str) -> usize {
+    text.char_indices()
+        .find(|(_, c)| !is_word_char(*c) && *c != '.')
+        .map_or(text.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(line: &str, language: Language) -> Vec<(TokenKind, &str)> {
+        tokenize(line, language)[0]
+            .iter()
+            .map(|token| (token.kind, &line[token.range.clone()]))
+            .collect()
+    }
+
+    #[test]
+    fn languages_are_detected() {
+        let rust = "fn main() {\n    let mut total = 0;\n    println!(\"{}\", total);\n}";
+        assert_eq!(detect(rust), Some(Language::Rust));
+        let python = "def add(a, b):\n    return a + b\n";
+        assert_eq!(detect(python), Some(Language::Python));
+        let sql = "SELECT name, count(*) FROM users WHERE active = 1 GROUP BY name;";
+        assert_eq!(detect(sql), Some(Language::Sql));
+        assert_eq!(detect("{\"a\": [1, 2], \"b\": null}"), Some(Language::Json));
+        assert_eq!(detect("#!/usr/bin/env bash\nls"), Some(Language::Shell));
+        assert_eq!(detect("-- report.sql\nselect 1"), Some(Language::Sql));
+        assert_eq!(
+            detect("const f = (x) => x === 1;"),
+            Some(Language::JavaScript)
+        );
+    }
+
+    #[test]
+    fn prose_is_not_detected() {
+        assert_eq!(
+            detect("Select the file from the menu where it says Open."),
+            None
+        );
+        assert_eq!(
+            detect("I set up node.js, let me know if the function works"),
+            None
+        );
+        assert_eq!(detect("[citation needed]"), None);
+    }
+
+    #[test]
+    fn lines_are_tokenized() {
+        use TokenKind::*;
+        assert_eq!(
+            tokens("let x = \"a // \\\"b\"; // c", Language::Rust),
+            [
+                (Keyword, "let"),
+                (Plain, " x = "),
+                (String, "\"a // \\\"b\""),
+                (Plain, "; "),
+                (Comment, "// c"),
+            ]
+        );
+        assert_eq!(
+            tokens("select * FROM t where id = 0x1f", Language::Sql),
+            [
+                (Keyword, "select"),
+                (Plain, " * "),
+                (Keyword, "FROM"),
+                (Plain, " t "),
+                (Keyword, "where"),
+                (Plain, " id = "),
+                (Number, "0x1f"),
+            ]
+        );
+        assert_eq!(
+            tokens("{\"a\": 1.5, \"b\": \"c\"}", Language::Json),
+            [
+                (Plain, "{"),
+                (Key, "\"a\""),
+                (Plain, ": "),
+                (Number, "1.5"),
+                (Plain, ", "),
+                (Key, "\"b\""),
+                (Plain, ": "),
+                (String, "\"c\""),
+                (Plain, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_and_strings_go_on_to_the_next_line() {
+        let lines = tokenize("/* a\nb */ fn", Language::Rust);
+        assert_eq!(
+            lines[0],
+            [Token {
+                kind: TokenKind::Comment,
+                range: 0..4
+            }]
+        );
+        assert_eq!(
+            lines[1],
+            [
+                Token {
+                    kind: TokenKind::Comment,
+                    range: 0..4
+                },
+                Token {
+                    kind: TokenKind::Plain,
+                    range: 4..5
+                },
+                Token {
+                    kind: TokenKind::Keyword,
+                    range: 5..7
+                },
+            ]
+        );
+
+        let lines = tokenize("s = \"\"\"x\ny\"\"\" + 1", Language::Python);
+        assert_eq!(
+            lines[1][0],
+            Token {
+                kind: TokenKind::String,
+                range: 0..4
+            }
+        );
+        assert_eq!(lines[1].last().unwrap().kind, TokenKind::Number);
+    }
+}
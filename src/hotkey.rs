@@ -0,0 +1,326 @@
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::{
+    AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, GrabMode, Keycode, ModMask, Window,
+};
+use x11rb::protocol::{ErrorKind, Event};
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+
+// A key combination grabbed for the whole X11 display, e.g. "Super+V"
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hotkey {
+    modifiers: ModMask,
+    keysym: u32,
+}
+
+impl Hotkey {
+    // Modifiers are Super, Ctrl, Alt and Shift, the key is a letter, a digit,
+    // F1 to F12 or one of a few named keys like Space or Insert
+    pub fn parse(hotkey: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = hotkey.split('+').map(|part| part.trim()).collect();
+        let key_name = parts.pop().unwrap_or_default();
+        let keysym = keysym_from_name(key_name)
+            .ok_or_else(|| format!("Unknown key {} in {}", key_name, hotkey))?;
+
+        let mut modifiers = ModMask::from(0u16);
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "super" | "win" | "meta" | "mod4" => ModMask::M4,
+                "ctrl" | "control" => ModMask::CONTROL,
+                "alt" | "mod1" => ModMask::M1,
+                "shift" => ModMask::SHIFT,
+                _ => return Err(format!("Unknown modifier {} in {}", modifier, hotkey)),
+            };
+        }
+        Ok(Self { modifiers, keysym })
+    }
+}
+
+fn keysym_from_name(name: &str) -> Option<u32> {
+    let lowercase_name = name.to_lowercase();
+    let mut chars = lowercase_name.chars();
+    // Letters and digits are their own (lowercase) keysym
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            return Some(c as u32);
+        }
+    }
+    if let Some(number) = lowercase_name
+        .strip_prefix('f')
+        .and_then(|number| number.parse::<u32>().ok())
+        .filter(|number| (1..=12).contains(number))
+    {
+        return Some(0xffbe + number - 1);
+    }
+    match lowercase_name.as_str() {
+        "space" => Some(0x0020),
+        "grave" | "`" => Some(0x0060),
+        "tab" => Some(0xff09),
+        "enter" | "return" => Some(0xff0d),
+        "pause" => Some(0xff13),
+        "esc" | "escape" => Some(0xff1b),
+        "home" => Some(0xff50),
+        "pageup" | "prior" => Some(0xff55),
+        "pagedown" | "next" => Some(0xff56),
+        "end" => Some(0xff57),
+        "print" => Some(0xff61),
+        "insert" => Some(0xff63),
+        _ => None,
+    }
+}
+
+// The keycode of the first key that types the keysym on the current layout
+pub fn keycode_of(connection: &RustConnection, keysym: u32) -> Option<Keycode> {
+    let setup = connection.setup();
+    let count = setup.max_keycode - setup.min_keycode + 1;
+    let mapping = connection
+        .get_keyboard_mapping(setup.min_keycode, count)
+        .ok()?
+        .reply()
+        .ok()?;
+    let per_keycode = mapping.keysyms_per_keycode as usize;
+    mapping
+        .keysyms
+        .chunks(per_keycode)
+        .position(|keysyms| keysyms.contains(&keysym))
+        .map(|index| setup.min_keycode + index as u8)
+}
+
+// The connection the hotkey is grabbed on, kept so the hotkey can be changed
+// while the thread of `listen` waits for presses
+struct Listener {
+    connection: Arc<RustConnection>,
+    root: Window,
+    // The modifiers and key grabbed, None while the hotkey is disabled
+    grab: Option<(ModMask, Keycode)>,
+}
+
+static LISTENER: Lazy<Mutex<Option<Listener>>> = Lazy::new(|| Mutex::new(None));
+
+// Grabs the hotkey and calls `on_press` from a thread of its own every time
+// it's pressed. Fails if there's no X server or another application has
+// grabbed the same keys. An empty hotkey grabs nothing until one is set.
+pub fn listen<F>(hotkey: &str, on_press: F) -> Result<(), String>
+where
+    F: Fn() + Send + 'static,
+{
+    let (connection, screen_num) =
+        x11rb::connect(None).map_err(|e| format!("Failed to connect to X11: {}", e))?;
+    let root = connection.setup().roots[screen_num].root;
+    let connection = Arc::new(connection);
+    *LISTENER.lock().unwrap() = Some(Listener {
+        connection: connection.clone(),
+        root,
+        grab: None,
+    });
+
+    std::thread::spawn(move || {
+        let is_grabbed = |keycode| {
+            let listener = LISTENER.lock().unwrap();
+            let grab = listener.as_ref().and_then(|listener| listener.grab);
+            grab.is_some_and(|(_, grabbed_keycode)| grabbed_keycode == keycode)
+        };
+        // Holding the keys down repeats a release and a press at the same time,
+        // which isn't a new press
+        let mut last_release_time = None;
+        loop {
+            match connection.wait_for_event() {
+                Ok(Event::KeyPress(event)) if is_grabbed(event.detail) => {
+                    if last_release_time != Some(event.time) {
+                        on_press();
+                    }
+                }
+                Ok(Event::KeyRelease(event)) if is_grabbed(event.detail) => {
+                    last_release_time = Some(event.time);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Lost the X11 connection of the global hotkey: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+    set_hotkey(hotkey)
+}
+
+// Releases the hotkey grabbed by `listen` and grabs this one instead, empty
+// disables it
+pub fn set_hotkey(hotkey: &str) -> Result<(), String> {
+    let hotkey = match hotkey {
+        "" => None,
+        hotkey => Some(Hotkey::parse(hotkey)?),
+    };
+    let mut listener = LISTENER.lock().unwrap();
+    let listener = listener
+        .as_mut()
+        .ok_or("The global hotkey needs an X11 display")?;
+
+    if let Some((modifiers, keycode)) = listener.grab.take() {
+        ungrab(listener, modifiers, keycode);
+    }
+    let Some(hotkey) = hotkey else {
+        return Ok(());
+    };
+    let keycode = keycode_of(&listener.connection, hotkey.keysym)
+        .ok_or("The key isn't on the keyboard layout")?;
+
+    for lock_modifier in lock_modifiers() {
+        let result = listener
+            .connection
+            .grab_key(
+                false,
+                listener.root,
+                hotkey.modifiers | lock_modifier,
+                keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )
+            .map_err(|e| e.to_string())?
+            .check();
+        let error = match result {
+            Ok(()) => continue,
+            Err(ReplyError::X11Error(error)) if error.error_kind == ErrorKind::Access => {
+                "The hotkey is already used by another application".to_string()
+            }
+            Err(e) => format!("Failed to grab the hotkey: {}", e),
+        };
+        // Some of the combinations may have been grabbed already
+        ungrab(listener, hotkey.modifiers, keycode);
+        return Err(error);
+    }
+    listener.grab = Some((hotkey.modifiers, keycode));
+    Ok(())
+}
+
+// The grab only matches the exact modifiers, so it's repeated with
+// Caps Lock and Num Lock (usually Mod2) on
+fn lock_modifiers() -> [ModMask; 4] {
+    [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+fn ungrab(listener: &Listener, modifiers: ModMask, keycode: Keycode) {
+    for lock_modifier in lock_modifiers() {
+        let _ = listener
+            .connection
+            .ungrab_key(keycode, listener.root, modifiers | lock_modifier);
+    }
+    let _ = listener.connection.flush();
+}
+
+// The window the window manager says is the active one
+pub fn active_window() -> Option<Window> {
+    let (connection, screen_num) = x11rb::connect(None).ok()?;
+    let root = connection.setup().roots[screen_num].root;
+    let atom = intern_atom(&connection, "_NET_ACTIVE_WINDOW")?;
+    let reply = connection
+        .get_property(false, root, atom, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    let active_window = reply.value32()?.next();
+    active_window.filter(|window| *window != x11rb::NONE)
+}
+
+// Asks the window manager to raise and focus the window, which winit can't do.
+// The window manager may still refuse to.
+pub fn activate_window(window: Window) -> Result<(), String> {
+    let (connection, screen_num) =
+        x11rb::connect(None).map_err(|e| format!("Failed to connect to X11: {}", e))?;
+    let root = connection.setup().roots[screen_num].root;
+    let atom = intern_atom(&connection, "_NET_ACTIVE_WINDOW")
+        .ok_or("Failed to get the _NET_ACTIVE_WINDOW atom")?;
+
+    // Source 2 tells the window manager the request comes from the user
+    let event = ClientMessageEvent::new(32, window, atom, [2, CURRENT_TIME, 0, 0, 0]);
+    let mask = EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY;
+    connection
+        .send_event(false, root, mask, event)
+        .map_err(|e| e.to_string())?;
+    connection.flush().map_err(|e| e.to_string())
+}
+
+fn intern_atom(connection: &RustConnection, name: &str) -> Option<u32> {
+    Some(
+        connection
+            .intern_atom(false, name.as_bytes())
+            .ok()?
+            .reply()
+            .ok()?
+            .atom,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use x11rb::protocol::xproto::{KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+    use x11rb::protocol::xtest::ConnectionExt as _;
+
+    #[test]
+    fn hotkeys_parse() {
+        let hotkey = Hotkey::parse("Super+V").unwrap();
+        assert_eq!(hotkey.modifiers, ModMask::M4);
+        assert_eq!(hotkey.keysym, 'v' as u32);
+
+        let hotkey = Hotkey::parse("ctrl + alt + F12").unwrap();
+        assert_eq!(hotkey.modifiers, ModMask::CONTROL | ModMask::M1);
+        assert_eq!(hotkey.keysym, 0xffc9);
+
+        assert!(Hotkey::parse("Hyper+V").is_err());
+        assert!(Hotkey::parse("Super+").is_err());
+        assert!(Hotkey::parse("Super+F13").is_err());
+    }
+
+    // Presses the keys of the hotkey with XTest and waits for the callback,
+    // then again once the hotkey is changed
+    #[test]
+    #[ignore = "needs an X server, e.g. xvfb-run cargo test -- --ignored"]
+    fn synthesized_key_presses_trigger_the_hotkey() {
+        let (tx, rx) = mpsc::channel();
+        listen("Ctrl+Alt+F12", move || tx.send(()).unwrap()).unwrap();
+
+        let (connection, _) = x11rb::connect(None).unwrap();
+        let control = keycode_of(&connection, 0xffe3).unwrap(); // Control_L
+        let alt = keycode_of(&connection, 0xffe9).unwrap(); // Alt_L
+        let press_keys = |key| {
+            let keys = [control, alt, key];
+            let press = |event_type, keycode| {
+                connection
+                    .xtest_fake_input(event_type, keycode, CURRENT_TIME, x11rb::NONE, 0, 0, 0)
+                    .unwrap();
+            };
+            for keycode in keys {
+                press(KEY_PRESS_EVENT, keycode);
+            }
+            for keycode in keys.iter().rev() {
+                press(KEY_RELEASE_EVENT, *keycode);
+            }
+            connection.flush().unwrap();
+        };
+        let f11 = keycode_of(&connection, 0xffc8).unwrap();
+        let f12 = keycode_of(&connection, 0xffc9).unwrap();
+
+        press_keys(f12);
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+        // Once per press
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        // The old hotkey is released when it's changed
+        set_hotkey("Ctrl+Alt+F11").unwrap();
+        press_keys(f12);
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        press_keys(f11);
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+    }
+}
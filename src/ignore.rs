@@ -0,0 +1,291 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::picker;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnoreRuleKind {
+    // The value is a regular expression the content is searched for
+    Pattern,
+    // The value is the size in bytes the content may not exceed
+    MaxBytes,
+    // The value is the window class or process name of the copying application
+    Application,
+    // The value is a shell command, whose output is ignored when copied
+    Command,
+}
+
+impl IgnoreRuleKind {
+    pub const ALL: [IgnoreRuleKind; 4] = [
+        IgnoreRuleKind::Pattern,
+        IgnoreRuleKind::MaxBytes,
+        IgnoreRuleKind::Application,
+        IgnoreRuleKind::Command,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IgnoreRuleKind::Pattern => "Pattern",
+            IgnoreRuleKind::MaxBytes => "Longer Than (Bytes)",
+            IgnoreRuleKind::Application => "Application",
+            IgnoreRuleKind::Command => "Command Output",
+        }
+    }
+}
+
+// Clipboard content matching any of the rules is never added to the history
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IgnoreRule {
+    pub kind: IgnoreRuleKind,
+    pub value: String,
+    // The pattern of Pattern rules, compiled when the rule is created, loaded or
+    // saved rather than for every clip
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+impl IgnoreRule {
+    pub fn new(kind: IgnoreRuleKind, value: &str) -> Self {
+        let mut rule = Self {
+            kind,
+            value: value.to_string(),
+            regex: None,
+        };
+        rule.compile();
+        rule
+    }
+
+    // Has to be called again after the kind or the value are changed
+    pub fn compile(&mut self) {
+        self.regex = match self.kind {
+            IgnoreRuleKind::Pattern => Regex::new(&self.value).ok(),
+            _ => None,
+        };
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.value.trim().is_empty() {
+            return Err(format!("{} rules need a value", self.kind.label()));
+        }
+
+        match self.kind {
+            IgnoreRuleKind::Pattern => Regex::new(&self.value)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid pattern {}: {}", self.value, e)),
+            IgnoreRuleKind::MaxBytes => self
+                .value
+                .trim()
+                .parse::<usize>()
+                .map(|_| ())
+                .map_err(|_| format!("Invalid number of bytes: {}", self.value)),
+            IgnoreRuleKind::Application | IgnoreRuleKind::Command => Ok(()),
+        }
+    }
+
+    // `owner` holds the names of the application that copied the content,
+    // e.g. its window class and process name
+    pub fn matches(&self, text: &str, owner: &[String]) -> bool {
+        match self.kind {
+            IgnoreRuleKind::Pattern => self
+                .regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(text)),
+            IgnoreRuleKind::MaxBytes => match self.value.trim().parse::<usize>() {
+                Ok(max_bytes) => text.len() > max_bytes,
+                Err(_) => false,
+            },
+            IgnoreRuleKind::Application => {
+                let application = self.value.trim();
+                owner
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(application))
+            }
+            IgnoreRuleKind::Command => {
+                command_output(&self.value).is_some_and(|output| output.trim() == text)
+            }
+        }
+    }
+}
+
+// Rules are checked on the thread that captures clips, so a command that hangs,
+// e.g. waiting for a passphrase, can't hold up capture for longer than this
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The output of the command if it succeeds in time, otherwise it's killed
+fn command_output(command: &str) -> Option<String> {
+    let mut process = picker::shell_command(command);
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    // Its own process group, so whatever the shell started is killed with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut process, 0);
+    let mut child = process.spawn().ok()?;
+
+    // Read from another thread, the command may wait for the pipe to be emptied
+    let mut stdout = child.stdout.take()?;
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.read_to_end(&mut output);
+        let _ = tx.send(output);
+    });
+
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            _ => break None,
+        }
+    };
+    let Some(status) = status else {
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(child.id() as i32), libc::SIGKILL);
+        }
+        #[cfg(not(unix))]
+        let _ = child.kill();
+        let _ = child.wait();
+        return None;
+    };
+
+    // Something the command left running in the background may still hold the pipe
+    let output = rx
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .ok()?;
+    status
+        .success()
+        .then(|| String::from_utf8_lossy(&output).into_owned())
+}
+
+pub fn is_ignored(rules: &[IgnoreRule], text: &str, owner: &[String]) -> bool {
+    rules.iter().any(|rule| rule.matches(text, owner))
+}
+
+// Images have no text to match, only the application that copied them
+pub fn is_image_ignored(rules: &[IgnoreRule], owner: &[String]) -> bool {
+    rules
+        .iter()
+        .filter(|rule| rule.kind == IgnoreRuleKind::Application)
+        .any(|rule| rule.matches("", owner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn pattern_rules_search_the_text() {
+        let rule = IgnoreRule::new(IgnoreRuleKind::Pattern, r"^\d{6}$");
+        assert!(rule.matches("123456", &[]));
+        assert!(!rule.matches("1234567", &[]));
+        assert!(!rule.matches("code 123456", &[]));
+    }
+
+    #[test]
+    fn loaded_patterns_are_compiled() {
+        let mut rule: IgnoreRule =
+            serde_json::from_str(r#"{"kind": "pattern", "value": "^\\d{6}$"}"#).unwrap();
+        assert!(!rule.matches("123456", &[]));
+        rule.compile();
+        assert!(rule.matches("123456", &[]));
+
+        rule.value = "^\\d{4}$".to_string();
+        rule.compile();
+        assert!(rule.matches("1234", &[]));
+        assert!(!rule.matches("123456", &[]));
+    }
+
+    #[test]
+    fn max_bytes_rules_count_bytes() {
+        let rule = IgnoreRule::new(IgnoreRuleKind::MaxBytes, "4");
+        assert!(!rule.matches("abcd", &[]));
+        assert!(rule.matches("abcde", &[]));
+        // "é" takes two bytes in UTF-8
+        assert!(rule.matches("abcé", &[]));
+    }
+
+    #[test]
+    fn application_rules_match_any_owner_name() {
+        let rule = IgnoreRule::new(IgnoreRuleKind::Application, "keepassxc");
+        assert!(rule.matches("secret", &owner(&["KeePassXC", "keepassxc-bin"])));
+        assert!(!rule.matches("secret", &owner(&["firefox"])));
+        assert!(!rule.matches("secret", &[]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_rules_match_the_command_output() {
+        let rule = IgnoreRule::new(IgnoreRuleKind::Command, "echo ignored");
+        assert!(rule.matches("ignored", &[]));
+        assert!(!rule.matches("kept", &[]));
+
+        // Failing commands never match
+        let rule = IgnoreRule::new(IgnoreRuleKind::Command, "echo ignored; exit 1");
+        assert!(!rule.matches("ignored", &[]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_rules_time_out() {
+        let rule = IgnoreRule::new(IgnoreRuleKind::Command, "echo ignored; sleep 30");
+        let started = Instant::now();
+        assert!(!rule.matches("ignored", &[]));
+        assert!(started.elapsed() < COMMAND_TIMEOUT + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected_and_never_match() {
+        let invalid_rules = [
+            IgnoreRule::new(IgnoreRuleKind::Pattern, "("),
+            IgnoreRule::new(IgnoreRuleKind::MaxBytes, "ten"),
+            IgnoreRule::new(IgnoreRuleKind::Application, " "),
+        ];
+        for rule in &invalid_rules {
+            assert!(rule.validate().is_err());
+        }
+        assert!(!is_ignored(&invalid_rules, "(ten)", &owner(&[" "])));
+
+        assert!(IgnoreRule::new(IgnoreRuleKind::Pattern, "a+")
+            .validate()
+            .is_ok());
+        assert!(IgnoreRule::new(IgnoreRuleKind::MaxBytes, "1024")
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn content_is_ignored_if_any_rule_matches() {
+        let rules = [
+            IgnoreRule::new(IgnoreRuleKind::Pattern, "^password"),
+            IgnoreRule::new(IgnoreRuleKind::MaxBytes, "10"),
+        ];
+        assert!(is_ignored(&rules, "password1", &[]));
+        assert!(is_ignored(&rules, "a long piece of text", &[]));
+        assert!(!is_ignored(&rules, "short", &[]));
+        assert!(!is_ignored(&[], "anything", &[]));
+    }
+
+    #[test]
+    fn images_are_only_ignored_by_application() {
+        let rules = [
+            IgnoreRule::new(IgnoreRuleKind::Pattern, ".*"),
+            IgnoreRule::new(IgnoreRuleKind::MaxBytes, "0"),
+            IgnoreRule::new(IgnoreRuleKind::Application, "flameshot"),
+        ];
+        assert!(is_image_ignored(&rules, &owner(&["flameshot"])));
+        assert!(!is_image_ignored(&rules, &owner(&["gimp"])));
+        assert!(!is_image_ignored(&rules, &[]));
+    }
+}
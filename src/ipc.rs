@@ -0,0 +1,181 @@
+#[cfg(unix)]
+use directories::ProjectDirs;
+#[cfg(unix)]
+use std::fs::{self, File};
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use crate::cli::Command;
+#[cfg(unix)]
+use crate::cli::{self, Context};
+#[cfg(unix)]
+use crate::history::ClipboardHistory;
+#[cfg(unix)]
+use crate::preferences::Config;
+#[cfg(unix)]
+use crate::window::WindowSender;
+
+// Runtime files (lock & socket) live in the runtime dir when there is one,
+// otherwise next to the history file.
+#[cfg(unix)]
+fn runtime_dir() -> PathBuf {
+    let project_dirs = ProjectDirs::from("", "", "clipstash").unwrap();
+    let dir = project_dirs
+        .runtime_dir()
+        .unwrap_or(project_dirs.data_dir())
+        .to_path_buf();
+
+    fs::create_dir_all(&dir).expect("Failed to create runtime directory");
+    dir
+}
+
+#[cfg(unix)]
+fn lock_file_path() -> PathBuf {
+    runtime_dir().join("clipstash.lock")
+}
+
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    runtime_dir().join("clipstash.sock")
+}
+
+// Held for as long as the process runs, the OS releases it when we exit.
+pub struct InstanceLock {
+    #[cfg(unix)]
+    _file: File,
+    #[cfg(target_os = "windows")]
+    _mutex: winapi::um::winnt::HANDLE,
+}
+
+impl InstanceLock {
+    // Returns None if another instance already holds the lock
+    #[cfg(unix)]
+    pub fn acquire() -> Option<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::create(lock_file_path()).ok()?;
+        let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if result != 0 {
+            return None;
+        }
+
+        Some(Self { _file: file })
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn acquire() -> Option<Self> {
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::shared::winerror::ERROR_ALREADY_EXISTS;
+        use winapi::um::errhandlingapi::GetLastError;
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::synchapi::CreateMutexW;
+
+        let name: Vec<u16> = std::ffi::OsStr::new("Local\\clipstash-instance")
+            .encode_wide()
+            .chain(Some(0))
+            .collect();
+
+        let mutex = unsafe { CreateMutexW(std::ptr::null_mut(), 0, name.as_ptr()) };
+        if mutex.is_null() {
+            return None;
+        }
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            unsafe { CloseHandle(mutex) };
+            return None;
+        }
+
+        Some(Self { _mutex: mutex })
+    }
+}
+
+// Binds the IPC socket. Must only be called while holding the InstanceLock,
+// since it removes a socket left behind by an instance that didn't exit cleanly.
+#[cfg(unix)]
+pub fn bind() -> std::io::Result<UnixListener> {
+    let path = socket_path();
+    let _ = fs::remove_file(&path);
+    UnixListener::bind(path)
+}
+
+// Handles commands sent by other ClipStash processes. When there is no window
+// (e.g. in daemon mode) commands that need one are rejected.
+#[cfg(unix)]
+pub fn start_server(listener: UnixListener, window: Option<WindowSender>) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept IPC connection: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = handle_connection(stream, window.as_ref()) {
+                eprintln!("Failed to handle IPC connection: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(unix)]
+fn handle_connection(stream: UnixStream, window: Option<&WindowSender>) -> std::io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let context = match window {
+        Some(window) => Context::Window(window),
+        None => Context::Daemon,
+    };
+    let command = Command::from_line(line.trim_end_matches('\n'));
+    let result = match &command {
+        Ok(command) => cli::execute(command, &context),
+        Err(message) => Err(message.clone()),
+    };
+
+    // Replies are a status line followed by the output or error message
+    let reply = match result {
+        Ok(output) => format!("ok\n{}", output),
+        Err(message) => format!("error\n{}", message),
+    };
+    (&stream).write_all(reply.as_bytes())?;
+
+    if let (Ok(Command::Quit), Context::Daemon) = (&command, &context) {
+        // Waits for a save in progress to finish, and holds the locks so no
+        // other one starts before the process exits. Same order as the
+        // clipboard monitor, history first.
+        let _clip_history = ClipboardHistory::get_instance();
+        let _config = Config::get_instance();
+        std::process::exit(0);
+    }
+    Ok(())
+}
+
+// Sends a command to the running instance. Returns None if no instance is listening.
+#[cfg(unix)]
+pub fn send_command(command: &Command) -> Option<Result<String, String>> {
+    let mut stream = UnixStream::connect(socket_path()).ok()?;
+    stream
+        .write_all(format!("{}\n", command.to_line()).as_bytes())
+        .ok()?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).ok()?;
+
+    let (status, output) = reply.split_once('\n').unwrap_or((&reply, ""));
+    if status == "ok" {
+        Some(Ok(output.to_string()))
+    } else {
+        Some(Err(output.to_string()))
+    }
+}
+
+// There's no IPC socket on Windows yet, so commands always run in-process
+#[cfg(target_os = "windows")]
+pub fn send_command(_command: &Command) -> Option<Result<String, String>> {
+    None
+}
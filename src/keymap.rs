@@ -0,0 +1,500 @@
+use glium::glutin::event::VirtualKeyCode;
+use imgui::Ui;
+use std::collections::BTreeMap;
+
+// Everything that can be done from the keyboard in the main window
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    CopyAndHide,
+    Remove,
+    FocusSearch,
+    SelectAll,
+    ClearSelection,
+    Undo,
+    Redo,
+    ShowHelp,
+    // Copies the nth visible item, from 1 to 9
+    QuickCopy(usize),
+}
+
+impl Action {
+    // Every action, in the order they're listed in
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::Up,
+            Action::Down,
+            Action::PageUp,
+            Action::PageDown,
+            Action::First,
+            Action::Last,
+            Action::CopyAndHide,
+            Action::Remove,
+            Action::FocusSearch,
+            Action::SelectAll,
+            Action::ClearSelection,
+            Action::Undo,
+            Action::Redo,
+            Action::ShowHelp,
+        ];
+        actions.extend((1..=9).map(Action::QuickCopy));
+        actions
+    }
+
+    // The name used in preferences.json
+    pub fn name(&self) -> String {
+        match self {
+            Action::Up => "up".to_string(),
+            Action::Down => "down".to_string(),
+            Action::PageUp => "page_up".to_string(),
+            Action::PageDown => "page_down".to_string(),
+            Action::First => "first".to_string(),
+            Action::Last => "last".to_string(),
+            Action::CopyAndHide => "copy_and_hide".to_string(),
+            Action::Remove => "remove".to_string(),
+            Action::FocusSearch => "focus_search".to_string(),
+            Action::SelectAll => "select_all".to_string(),
+            Action::ClearSelection => "clear_selection".to_string(),
+            Action::Undo => "undo".to_string(),
+            Action::Redo => "redo".to_string(),
+            Action::ShowHelp => "show_help".to_string(),
+            Action::QuickCopy(n) => format!("quick_copy_{}", n),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::all()
+            .into_iter()
+            .find(|action| action.name() == name)
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Action::Up => "Select the previous clip".to_string(),
+            Action::Down => "Select the next clip".to_string(),
+            Action::PageUp => "Move up a page".to_string(),
+            Action::PageDown => "Move down a page".to_string(),
+            Action::First => "Select the first clip".to_string(),
+            Action::Last => "Select the last clip".to_string(),
+            Action::CopyAndHide => "Copy the selected clips and hide the window".to_string(),
+            Action::Remove => "Remove the selected clips".to_string(),
+            Action::FocusSearch => "Search".to_string(),
+            Action::SelectAll => "Select all clips".to_string(),
+            Action::ClearSelection => "Clear the selection, then the search".to_string(),
+            Action::Undo => "Undo".to_string(),
+            Action::Redo => "Redo".to_string(),
+            Action::ShowHelp => "Show this list".to_string(),
+            Action::QuickCopy(n) => format!("Copy clip {} and hide the window", n),
+        }
+    }
+
+    // Whether the action still works while typing in the search field,
+    // the others would get in the way of editing the text
+    pub fn works_while_typing(&self) -> bool {
+        matches!(
+            self,
+            Action::Up | Action::Down | Action::PageUp | Action::PageDown | Action::CopyAndHide
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyChord {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl KeyChord {
+    const fn key(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    const fn ctrl(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            shift: false,
+        }
+    }
+
+    const fn ctrl_shift(key: VirtualKeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            shift: true,
+        }
+    }
+
+    // Accepts labels like "Ctrl+Shift+Z" or "j", in any case
+    pub fn parse(label: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = label.split('+').map(|part| part.trim()).collect();
+        let key_name = parts.pop().unwrap_or_default();
+        let key = key_from_name(key_name)
+            .ok_or_else(|| format!("Unknown key {} in {}", key_name, label))?;
+
+        let mut chord = Self::key(key);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                _ => return Err(format!("Unknown modifier {} in {}", modifier, label)),
+            }
+        }
+        Ok(chord)
+    }
+
+    // e.g. "Ctrl+Shift+Z"
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+        label.push_str(&key_name(self.key));
+        label
+    }
+
+    // Whether pressing the chord in a text field types something
+    fn types_text(&self) -> bool {
+        !self.ctrl
+            && KEYS
+                .iter()
+                .any(|(_, key, printable)| *key == self.key && *printable)
+    }
+
+    // The modifiers have to match exactly, so Ctrl+Z doesn't also trigger Z.
+    // imgui-winit-support indexes the keys by their winit key code.
+    fn is_pressed(&self, ui: &Ui) -> bool {
+        let io = ui.io();
+        io.key_ctrl == self.ctrl
+            && io.key_shift == self.shift
+            && ui.is_key_index_pressed(self.key as i32)
+    }
+}
+
+// The keys that can be bound, with their name and whether they type text
+const KEYS: &[(&str, VirtualKeyCode, bool)] = &[
+    ("A", VirtualKeyCode::A, true),
+    ("B", VirtualKeyCode::B, true),
+    ("C", VirtualKeyCode::C, true),
+    ("D", VirtualKeyCode::D, true),
+    ("E", VirtualKeyCode::E, true),
+    ("F", VirtualKeyCode::F, true),
+    ("G", VirtualKeyCode::G, true),
+    ("H", VirtualKeyCode::H, true),
+    ("I", VirtualKeyCode::I, true),
+    ("J", VirtualKeyCode::J, true),
+    ("K", VirtualKeyCode::K, true),
+    ("L", VirtualKeyCode::L, true),
+    ("M", VirtualKeyCode::M, true),
+    ("N", VirtualKeyCode::N, true),
+    ("O", VirtualKeyCode::O, true),
+    ("P", VirtualKeyCode::P, true),
+    ("Q", VirtualKeyCode::Q, true),
+    ("R", VirtualKeyCode::R, true),
+    ("S", VirtualKeyCode::S, true),
+    ("T", VirtualKeyCode::T, true),
+    ("U", VirtualKeyCode::U, true),
+    ("V", VirtualKeyCode::V, true),
+    ("W", VirtualKeyCode::W, true),
+    ("X", VirtualKeyCode::X, true),
+    ("Y", VirtualKeyCode::Y, true),
+    ("Z", VirtualKeyCode::Z, true),
+    ("0", VirtualKeyCode::Key0, true),
+    ("1", VirtualKeyCode::Key1, true),
+    ("2", VirtualKeyCode::Key2, true),
+    ("3", VirtualKeyCode::Key3, true),
+    ("4", VirtualKeyCode::Key4, true),
+    ("5", VirtualKeyCode::Key5, true),
+    ("6", VirtualKeyCode::Key6, true),
+    ("7", VirtualKeyCode::Key7, true),
+    ("8", VirtualKeyCode::Key8, true),
+    ("9", VirtualKeyCode::Key9, true),
+    ("/", VirtualKeyCode::Slash, true),
+    (",", VirtualKeyCode::Comma, true),
+    (".", VirtualKeyCode::Period, true),
+    (";", VirtualKeyCode::Semicolon, true),
+    ("-", VirtualKeyCode::Minus, true),
+    ("=", VirtualKeyCode::Equals, true),
+    ("Space", VirtualKeyCode::Space, true),
+    ("Up", VirtualKeyCode::Up, false),
+    ("Down", VirtualKeyCode::Down, false),
+    ("Left", VirtualKeyCode::Left, false),
+    ("Right", VirtualKeyCode::Right, false),
+    ("Page Up", VirtualKeyCode::PageUp, false),
+    ("Page Down", VirtualKeyCode::PageDown, false),
+    ("Home", VirtualKeyCode::Home, false),
+    ("End", VirtualKeyCode::End, false),
+    ("Insert", VirtualKeyCode::Insert, false),
+    ("Delete", VirtualKeyCode::Delete, false),
+    ("Backspace", VirtualKeyCode::Back, false),
+    ("Enter", VirtualKeyCode::Return, false),
+    ("Tab", VirtualKeyCode::Tab, false),
+    ("Esc", VirtualKeyCode::Escape, false),
+    ("F1", VirtualKeyCode::F1, false),
+    ("F2", VirtualKeyCode::F2, false),
+    ("F3", VirtualKeyCode::F3, false),
+    ("F4", VirtualKeyCode::F4, false),
+    ("F5", VirtualKeyCode::F5, false),
+    ("F6", VirtualKeyCode::F6, false),
+    ("F7", VirtualKeyCode::F7, false),
+    ("F8", VirtualKeyCode::F8, false),
+    ("F9", VirtualKeyCode::F9, false),
+    ("F10", VirtualKeyCode::F10, false),
+    ("F11", VirtualKeyCode::F11, false),
+    ("F12", VirtualKeyCode::F12, false),
+];
+
+fn key_name(key: VirtualKeyCode) -> String {
+    KEYS.iter()
+        .find(|(_, known_key, _)| *known_key == key)
+        .map(|(name, _, _)| name.to_string())
+        .unwrap_or_else(|| format!("{:?}", key))
+}
+
+// Case and spaces don't matter, so "pageup" is "Page Up"
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    let normalize = |name: &str| name.replace(' ', "").to_lowercase();
+    let name = match normalize(name).as_str() {
+        "escape" => "esc".to_string(),
+        "return" => "enter".to_string(),
+        name => name.to_string(),
+    };
+    KEYS.iter()
+        .find(|(known_name, _, _)| normalize(known_name) == name)
+        .map(|(_, key, _)| *key)
+}
+
+const NUMBER_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+// The keyboard shortcuts of the main window. An action may have more than
+// one, or none.
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, KeyChord)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings = vec![
+            (Action::Up, KeyChord::key(VirtualKeyCode::Up)),
+            (Action::Down, KeyChord::key(VirtualKeyCode::Down)),
+            (Action::PageUp, KeyChord::key(VirtualKeyCode::PageUp)),
+            (Action::PageDown, KeyChord::key(VirtualKeyCode::PageDown)),
+            (Action::First, KeyChord::key(VirtualKeyCode::Home)),
+            (Action::Last, KeyChord::key(VirtualKeyCode::End)),
+            (Action::CopyAndHide, KeyChord::key(VirtualKeyCode::Return)),
+            (Action::Remove, KeyChord::key(VirtualKeyCode::Delete)),
+            (Action::FocusSearch, KeyChord::key(VirtualKeyCode::Slash)),
+            (Action::SelectAll, KeyChord::ctrl(VirtualKeyCode::A)),
+            (
+                Action::ClearSelection,
+                KeyChord::key(VirtualKeyCode::Escape),
+            ),
+            (Action::Undo, KeyChord::ctrl(VirtualKeyCode::Z)),
+            (Action::Redo, KeyChord::ctrl(VirtualKeyCode::Y)),
+            (Action::Redo, KeyChord::ctrl_shift(VirtualKeyCode::Z)),
+            (Action::ShowHelp, KeyChord::key(VirtualKeyCode::F1)),
+        ];
+        for (i, key) in NUMBER_KEYS.into_iter().enumerate() {
+            bindings.push((Action::QuickCopy(i + 1), KeyChord::key(key)));
+        }
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    // The defaults, with the actions in `bindings` bound to the shortcuts given
+    // there instead, e.g. { "down": ["Down", "J"] }. Fails if a shortcut can't be
+    // parsed or is used for more than one action.
+    pub fn from_config(bindings: &BTreeMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        for (name, labels) in bindings {
+            let action =
+                Action::from_name(name).ok_or_else(|| format!("Unknown action: {}", name))?;
+            let chords = labels
+                .iter()
+                .map(|label| KeyChord::parse(label))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            keymap
+                .bindings
+                .retain(|(bound_action, _)| *bound_action != action);
+            keymap
+                .bindings
+                .extend(chords.into_iter().map(|chord| (action, chord)));
+        }
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    // The actions bound differently from the defaults, as stored in preferences.json
+    pub fn to_config(&self) -> BTreeMap<String, Vec<String>> {
+        let defaults = Self::default();
+        Action::all()
+            .into_iter()
+            .filter(|action| self.chords(*action) != defaults.chords(*action))
+            .map(|action| {
+                let labels = self
+                    .chords(action)
+                    .iter()
+                    .map(|chord| chord.label())
+                    .collect();
+                (action.name(), labels)
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, (action, chord)) in self.bindings.iter().enumerate() {
+            let conflict = self.bindings[i + 1..]
+                .iter()
+                .find(|(other_action, other_chord)| other_chord == chord && other_action != action);
+            if let Some((other_action, _)) = conflict {
+                return Err(format!(
+                    "{} is bound to both {} and {}",
+                    chord.label(),
+                    action.name(),
+                    other_action.name()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn chords(&self, action: Action) -> Vec<KeyChord> {
+        self.bindings
+            .iter()
+            .filter(|(bound_action, _)| *bound_action == action)
+            .map(|(_, chord)| *chord)
+            .collect()
+    }
+
+    // Each action with all of its shortcuts, e.g. ("Ctrl+Y / Ctrl+Shift+Z", "Redo")
+    pub fn shortcuts(&self) -> Vec<(String, String)> {
+        let mut shortcuts: Vec<(Action, Vec<String>)> = Vec::new();
+        for (action, chord) in &self.bindings {
+            match shortcuts.iter_mut().find(|(listed, _)| listed == action) {
+                Some((_, labels)) => labels.push(chord.label()),
+                None => shortcuts.push((*action, vec![chord.label()])),
+            }
+        }
+        shortcuts
+            .into_iter()
+            .map(|(action, labels)| (labels.join(" / "), action.description()))
+            .collect()
+    }
+
+    // The action whose shortcut was pressed this frame, if any. While typing,
+    // keys that type text are left to the text field.
+    pub fn pressed_action(&self, ui: &Ui, typing: bool) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|(action, chord)| {
+                !typing || (action.works_while_typing() && !chord.types_text())
+            })
+            .find(|(_, chord)| chord.is_pressed(ui))
+            .map(|(action, _)| *action)
+    }
+
+    // The first shortcut of the action, shown in the menus
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(bound_action, _)| *bound_action == action)
+            .map(|(_, chord)| chord.label())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_list_every_chord_of_an_action() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.label(Action::Redo), "Ctrl+Y");
+
+        let shortcuts = keymap.shortcuts();
+        let shortcut = |keys: &str, action: Action| (keys.to_string(), action.description());
+        assert!(shortcuts.contains(&shortcut("Ctrl+Y / Ctrl+Shift+Z", Action::Redo)));
+        assert!(shortcuts.contains(&shortcut("Page Up", Action::PageUp)));
+        assert!(shortcuts.contains(&shortcut("/", Action::FocusSearch)));
+        assert!(shortcuts.contains(&shortcut("9", Action::QuickCopy(9))));
+    }
+
+    fn bindings(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(name, labels)| {
+                let labels = labels.iter().map(|label| label.to_string()).collect();
+                (name.to_string(), labels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chords_parse_back_from_their_labels() {
+        for (_, chord) in Keymap::default().bindings {
+            assert!(KeyChord::parse(&chord.label()) == Ok(chord));
+        }
+        let chord = KeyChord::parse("ctrl+shift+pageup").unwrap();
+        assert_eq!(chord.label(), "Ctrl+Shift+Page Up");
+        assert!(KeyChord::parse("Hyper+J").is_err());
+        assert!(KeyChord::parse("Ctrl+").is_err());
+    }
+
+    #[test]
+    fn overrides_replace_the_default_shortcuts() {
+        let config = bindings(&[
+            ("down", &["Down", "J"]),
+            ("up", &["Up", "K"]),
+            ("show_help", &[]),
+        ]);
+        let keymap = Keymap::from_config(&config).unwrap();
+        assert_eq!(keymap.label(Action::Down), "Down");
+        assert!(keymap
+            .chords(Action::Down)
+            .contains(&KeyChord::parse("j").unwrap()));
+        assert!(keymap.chords(Action::ShowHelp).is_empty());
+        // Only what differs from the defaults is stored
+        assert!(keymap.to_config() == config);
+    }
+
+    #[test]
+    fn conflicting_or_unknown_bindings_are_rejected() {
+        let config = bindings(&[("down", &["Ctrl+Z"])]);
+        assert_eq!(
+            Keymap::from_config(&config).err().unwrap(),
+            "Ctrl+Z is bound to both undo and down"
+        );
+        assert!(Keymap::from_config(&bindings(&[("fly", &["F"])])).is_err());
+        assert!(Keymap::from_config(&bindings(&[("down", &["Hyper"])])).is_err());
+        // Rebinding the same key to another action works once it's freed up
+        let config = bindings(&[("remove", &["D"]), ("quick_copy_1", &["Delete"])]);
+        assert!(Keymap::from_config(&config).is_ok());
+    }
+}
@@ -1,26 +1,90 @@
 #![windows_subsystem = "windows"]
 
+mod cli;
+mod content;
+mod crypto;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod export;
+mod highlight;
 mod history;
+#[cfg(target_os = "linux")]
+mod hotkey;
+mod ignore;
+mod import;
+mod ipc;
+mod keymap;
+mod paste;
+mod pause;
+mod picker;
+mod platform;
 mod preferences;
+mod sensitive;
+mod table;
+mod tray;
 mod ui;
 mod window;
+mod window_lock;
 
 use arboard::Clipboard;
 use auto_launch::AutoLaunchBuilder;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 use std::{cell::RefCell, env};
 use window::{ImguiWindow, Skin};
 
 use crate::ui::UI;
 
+// Larger images aren't captured, e.g. 4096×4096 is 64 MiB once decoded
+const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
 fn main() {
     let args: Vec<_> = env::args().collect();
+    let mut daemon = false;
     if args.len() > 1 {
         // If the app auto-started, the window might glitch out if it's opened immediately
         // This is not optimal, but it's a quick fix for now
         if args[1] == "--auto-started" {
             std::thread::sleep(std::time::Duration::from_secs(5));
+        } else if args[1] == "--daemon" {
+            daemon = true;
+        } else {
+            std::process::exit(cli::run(&args[1..]));
+        }
+    }
+
+    // Only one instance may monitor the clipboard and write the history file,
+    // so if one is already running just bring its window up instead.
+    let _instance_lock = match ipc::InstanceLock::acquire() {
+        Some(lock) => lock,
+        None => {
+            if daemon {
+                eprintln!("ClipStash is already running");
+                std::process::exit(1);
+            }
+            if let Some(Err(e)) = ipc::send_command(&cli::Command::Show) {
+                eprintln!("Failed to show the running instance: {}", e);
+            }
+            return;
         }
+    };
+
+    #[cfg(unix)]
+    let ipc_listener = ipc::bind().expect("Failed to bind IPC socket");
+
+    #[cfg(target_os = "linux")]
+    if let Err(e) = dbus::start() {
+        eprintln!("Failed to start D-Bus service: {}", e);
+    }
+
+    // The daemon only captures the clipboard and answers commands, so it never
+    // touches the display, imgui or the system tray.
+    if daemon {
+        #[cfg(unix)]
+        ipc::start_server(ipc_listener, None);
+        monitor_clipboard();
+        return;
     }
 
     std::thread::spawn(monitor_clipboard);
@@ -52,9 +116,35 @@ fn main() {
         header_hovered: pink_rgba,
         header_active: deep_pink,
         frame_rounding: 1.0,
+        syntax: highlight::Theme {
+            keyword: [0.949, 0.31, 0.82, 1.0],
+            string: [0.55, 0.85, 0.6, 1.0],
+            number: [0.95, 0.75, 0.45, 1.0],
+            comment: [0.5, 0.5, 0.5, 1.0],
+            key: [0.75, 0.65, 1.0, 1.0],
+        },
     };
     window.set_skin(default_skin);
 
+    #[cfg(unix)]
+    ipc::start_server(ipc_listener, Some(window.get_sender()));
+
+    #[cfg(target_os = "linux")]
+    {
+        // Listens without a hotkey too, so one can be set from the window later
+        let global_hotkey = preferences::Config::get_instance().get_global_hotkey();
+        let sender = window.get_sender();
+        let on_press = move || sender.send(window::WindowMessage::Toggle);
+        if let Err(e) = hotkey::listen(&global_hotkey, on_press) {
+            if !global_hotkey.is_empty() {
+                eprintln!(
+                    "Failed to register the global hotkey {}: {}",
+                    global_hotkey, e
+                );
+            }
+        }
+    }
+
     window.render(move |imgui, display, platform, renderer, control_flow| {
         ui.borrow_mut()
             .on_draw(imgui, display, platform, renderer, control_flow);
@@ -71,17 +161,48 @@ fn monitor_clipboard() {
             }
         }
     };
-    let mut last_element = history::ClipboardHistory::get_instance()
-        .get_items()
-        .last()
-        .unwrap_or(&"".to_string())
-        .clone();
+    let last_item = history::ClipboardHistory::get_instance().get_items().pop();
+    let mut last_element = last_item
+        .as_ref()
+        .map(|item| item.text.clone())
+        .unwrap_or_default();
+    let mut last_image_hash = last_item
+        .and_then(|item| item.image.as_ref()?.to_rgba())
+        .map(|rgba| hash_bytes(&rgba));
+    let mut last_image_stamp = None;
+    let clipboard_hints = platform::ClipboardHints::new();
+    let mut last_expiry_check = std::time::Instant::now();
+    let mut last_image_check = std::time::Instant::now();
     loop {
         std::thread::sleep(std::time::Duration::from_millis(100));
 
+        // Sensitive items aren't on disk, so they only have to be removed from memory
+        if last_expiry_check.elapsed() >= std::time::Duration::from_secs(1) {
+            let lifetime = preferences::Config::get_instance().get_sensitive_clip_lifetime();
+            if lifetime > 0 {
+                history::ClipboardHistory::get_instance().remove_expired_items(lifetime);
+            }
+            last_expiry_check = std::time::Instant::now();
+        }
+
         let clipboard_contents = match clipboard.get_text() {
             Ok(contents) => contents,
             Err(_) => {
+                // Getting an image means decoding it, so it's only checked once a
+                // second, and only after a new copy when the platform can tell
+                if last_image_check.elapsed() >= std::time::Duration::from_secs(1) {
+                    let stamp = clipboard_hints.change_stamp();
+                    if stamp.is_none() || stamp != last_image_stamp {
+                        let item =
+                            get_new_image(&mut clipboard, &clipboard_hints, &mut last_image_hash);
+                        if let Some(item) = item {
+                            add_clip(item);
+                            last_element.clear();
+                        }
+                        last_image_stamp = stamp;
+                    }
+                    last_image_check = std::time::Instant::now();
+                }
                 std::thread::sleep(std::time::Duration::from_millis(200));
                 continue;
             }
@@ -96,18 +217,108 @@ fn monitor_clipboard() {
             continue;
         }
 
-        // Acquire a lock to the clipboard history & config only when we need to,
-        // and release it immediately, before the thread sleeps.
-        let mut clip_history = history::ClipboardHistory::get_instance();
-        let config = preferences::Config::get_instance();
+        // Copies made while paused are remembered as the last element without
+        // being captured, so they aren't picked up once capture resumes
+        if pause::Pause::get_instance().is_paused() {
+            last_element = trimmed_contents.to_string();
+            continue;
+        }
+
+        // Ignored content is remembered as the last element too, so the rules
+        // (which may run a command) aren't evaluated again until it changes
+        let mut owner_names = clipboard_hints.owner_names();
+        let ignore_rules = preferences::Config::get_instance().get_ignore_rules();
+        if !ignore_rules.is_empty()
+            && ignore::is_ignored(&ignore_rules, trimmed_contents, &owner_names)
+        {
+            last_element = trimmed_contents.to_string();
+            continue;
+        }
 
-        clip_history.add_item(trimmed_contents.to_string());
+        let mut item = history::ClipboardItem::new(0, trimmed_contents.to_string());
+        item.sensitive =
+            clipboard_hints.is_marked_sensitive() || sensitive::is_sensitive(trimmed_contents);
+        // The process name comes last, when it's known
+        item.source = owner_names.pop().unwrap_or_default();
+        add_clip(item);
         last_element = trimmed_contents.to_string();
+        last_image_hash = None;
+    }
+}
+
+// Returns the image on the clipboard if it's a new one. It isn't captured while
+// paused, over MAX_IMAGE_BYTES, or if an Application ignore rule matches.
+fn get_new_image(
+    clipboard: &mut Clipboard,
+    clipboard_hints: &platform::ClipboardHints,
+    last_image_hash: &mut Option<u64>,
+) -> Option<history::ClipboardItem> {
+    let image = clipboard.get_image().ok()?;
+    let hash = hash_bytes(&image.bytes);
+    if *last_image_hash == Some(hash) {
+        return None;
+    }
+    *last_image_hash = Some(hash);
+    if pause::Pause::get_instance().is_paused() || image.bytes.len() > MAX_IMAGE_BYTES {
+        return None;
+    }
+
+    let mut owner_names = clipboard_hints.owner_names();
+    let ignore_rules = preferences::Config::get_instance().get_ignore_rules();
+    if ignore::is_image_ignored(&ignore_rules, &owner_names) {
+        return None;
+    }
+
+    let clip_image =
+        history::ClipImage::from_rgba(image.width as u32, image.height as u32, &image.bytes)?;
+    let mut item = history::ClipboardItem::from_image(0, clip_image);
+    item.source = owner_names.pop().unwrap_or_default();
+    Some(item)
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn add_clip(item: history::ClipboardItem) {
+    // Acquire a lock to the clipboard history & config only when we need to,
+    // and release it immediately, before the thread sleeps.
+    let mut clip_history = history::ClipboardHistory::get_instance();
+    let config = preferences::Config::get_instance();
+
+    // No id while the history is locked, the item is only added once it's unlocked
+    let id = clip_history.add_item(item);
+
+    // Nothing copied while locked is shown on D-Bus, the same as sensitive clips.
+    // The history may have marked the item sensitive, so it's checked once added.
+    #[cfg(target_os = "linux")]
+    let new_clip = id.and_then(|id| {
+        let item = clip_history.get_item(clip_history.index_of(id)?);
+        let text = if item.sensitive || window_lock::WindowLock::get_instance().is_locked() {
+            history::MASKED_TEXT.to_string()
+        } else {
+            item.text.clone()
+        };
+        Some((id, text))
+    });
+    #[cfg(not(target_os = "linux"))]
+    let _ = id;
+
+    if config.get_save_history() {
+        clip_history
+            .save_to_file()
+            .expect("Failed to save history to file");
+    }
 
-        if config.get_save_history() {
-            clip_history
-                .save_to_file()
-                .expect("Failed to save history to file");
+    // D-Bus method calls lock the history too, so release it before emitting
+    #[cfg(target_os = "linux")]
+    {
+        drop(clip_history);
+        drop(config);
+        if let Some((id, text)) = new_clip {
+            dbus::notify_new_clip(id, &text);
         }
     }
 }
@@ -0,0 +1,227 @@
+// Pastes a picked clip into the window that was active before ClipStash was
+// shown, by giving it the focus back and pressing the paste keys in it
+
+use glium::glutin::window::Window;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// How long the previous window gets to take the focus back before the keys are pressed
+const FOCUS_DELAY: Duration = Duration::from_millis(150);
+
+// Platform id of the window that was active before ClipStash was shown
+static PREVIOUS_WINDOW: Mutex<Option<u64>> = Mutex::new(None);
+
+// The keys that paste in the previous window, terminals usually need Ctrl+Shift+V
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasteChord {
+    #[default]
+    CtrlV,
+    CtrlShiftV,
+    ShiftInsert,
+}
+
+impl PasteChord {
+    pub const ALL: [PasteChord; 3] = [
+        PasteChord::CtrlV,
+        PasteChord::CtrlShiftV,
+        PasteChord::ShiftInsert,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PasteChord::CtrlV => "Ctrl+V",
+            PasteChord::CtrlShiftV => "Ctrl+Shift+V",
+            PasteChord::ShiftInsert => "Shift+Insert",
+        }
+    }
+}
+
+// Called every time before ClipStash's window is shown. Showing it again
+// while it's active leaves nothing to paste into.
+pub fn remember_active_window(own_window: &Window) {
+    let own_window = platform_window::id_of(own_window);
+    *PREVIOUS_WINDOW.lock().unwrap() =
+        platform_window::active().filter(|window| Some(*window) != own_window);
+}
+
+// Runs on a thread of its own, so the window can be hidden in the meantime.
+// Pastes at most once each time the window is shown, and only once the window
+// that was active before has the focus back, so the keys can't go elsewhere.
+pub fn paste(chord: PasteChord) {
+    let Some(previous_window) = PREVIOUS_WINDOW.lock().unwrap().take() else {
+        return;
+    };
+    std::thread::spawn(move || {
+        let result = platform_window::activate(previous_window).and_then(|_| {
+            std::thread::sleep(FOCUS_DELAY);
+            if platform_window::active() != Some(previous_window) {
+                return Err("The previous window didn't take the focus back".to_string());
+            }
+            platform_window::press(chord)
+        });
+        if let Err(e) = result {
+            eprintln!("Failed to paste: {}", e);
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+mod platform_window {
+    use glium::glutin::platform::unix::WindowExtUnix;
+    use glium::glutin::window::Window;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+    use x11rb::protocol::xtest::ConnectionExt;
+    use x11rb::{CURRENT_TIME, NONE};
+
+    use super::PasteChord;
+    use crate::hotkey;
+
+    const CONTROL_L: u32 = 0xffe3;
+    const SHIFT_L: u32 = 0xffe1;
+    const INSERT: u32 = 0xff63;
+    const V: u32 = 0x0076;
+
+    pub fn id_of(window: &Window) -> Option<u64> {
+        window.xlib_window().map(|id| u64::from(id as u32))
+    }
+
+    pub fn active() -> Option<u64> {
+        hotkey::active_window().map(u64::from)
+    }
+
+    pub fn activate(window: u64) -> Result<(), String> {
+        hotkey::activate_window(window as u32)
+    }
+
+    // Presses the keys with XTest, as if they were typed on the keyboard
+    pub fn press(chord: PasteChord) -> Result<(), String> {
+        let (connection, _) =
+            x11rb::connect(None).map_err(|e| format!("Failed to connect to X11: {}", e))?;
+        let keysyms: &[u32] = match chord {
+            PasteChord::CtrlV => &[CONTROL_L, V],
+            PasteChord::CtrlShiftV => &[CONTROL_L, SHIFT_L, V],
+            PasteChord::ShiftInsert => &[SHIFT_L, INSERT],
+        };
+        let keycodes = keysyms
+            .iter()
+            .map(|keysym| hotkey::keycode_of(&connection, *keysym))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(format!("{} isn't on the keyboard layout", chord.label()))?;
+
+        let presses = keycodes.iter().map(|keycode| (KEY_PRESS_EVENT, *keycode));
+        let releases = keycodes
+            .iter()
+            .rev()
+            .map(|keycode| (KEY_RELEASE_EVENT, *keycode));
+        for (event_type, keycode) in presses.chain(releases) {
+            connection
+                .xtest_fake_input(event_type, keycode, CURRENT_TIME, NONE, 0, 0, 0)
+                .map_err(|e| e.to_string())?;
+        }
+        connection.flush().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform_window {
+    use glium::glutin::platform::windows::WindowExtWindows;
+    use glium::glutin::window::Window;
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{
+        GetForegroundWindow, SendInput, SetForegroundWindow, INPUT, INPUT_KEYBOARD, KEYBDINPUT,
+        KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, VK_CONTROL, VK_INSERT, VK_SHIFT,
+    };
+
+    use super::PasteChord;
+
+    pub fn id_of(window: &Window) -> Option<u64> {
+        Some(window.hwnd() as u64)
+    }
+
+    pub fn active() -> Option<u64> {
+        let window = unsafe { GetForegroundWindow() };
+        (!window.is_null()).then_some(window as u64)
+    }
+
+    // Windows only lets a process take the focus in some cases, e.g. while
+    // it's in the foreground itself
+    pub fn activate(window: u64) -> Result<(), String> {
+        if unsafe { SetForegroundWindow(window as HWND) } == 0 {
+            return Err("Failed to focus the previous window".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn press(chord: PasteChord) -> Result<(), String> {
+        let keys: &[i32] = match chord {
+            PasteChord::CtrlV => &[VK_CONTROL, 'V' as i32],
+            PasteChord::CtrlShiftV => &[VK_CONTROL, VK_SHIFT, 'V' as i32],
+            PasteChord::ShiftInsert => &[VK_SHIFT, VK_INSERT],
+        };
+
+        let presses = keys.iter().map(|key| (*key, 0));
+        let releases = keys.iter().rev().map(|key| (*key, KEYEVENTF_KEYUP));
+        let mut inputs: Vec<INPUT> = presses
+            .chain(releases)
+            .map(|(key, flags)| {
+                // Otherwise Insert is the one on the numeric keypad
+                let flags = match key {
+                    VK_INSERT => flags | KEYEVENTF_EXTENDEDKEY,
+                    _ => flags,
+                };
+                let mut input = INPUT {
+                    type_: INPUT_KEYBOARD,
+                    u: unsafe { std::mem::zeroed() },
+                };
+                unsafe {
+                    *input.u.ki_mut() = KEYBDINPUT {
+                        wVk: key as u16,
+                        wScan: 0,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    }
+                };
+                input
+            })
+            .collect();
+
+        let sent = unsafe {
+            SendInput(
+                inputs.len() as u32,
+                inputs.as_mut_ptr(),
+                std::mem::size_of::<INPUT>() as i32,
+            )
+        };
+        if sent as usize != inputs.len() {
+            return Err("The keys were blocked by another application".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform_window {
+    use glium::glutin::window::Window;
+
+    use super::PasteChord;
+
+    pub fn id_of(_window: &Window) -> Option<u64> {
+        None
+    }
+
+    pub fn active() -> Option<u64> {
+        None
+    }
+
+    pub fn activate(_window: u64) -> Result<(), String> {
+        Err("Pasting isn't supported on this platform".to_string())
+    }
+
+    pub fn press(_chord: PasteChord) -> Result<(), String> {
+        Err("Pasting isn't supported on this platform".to_string())
+    }
+}
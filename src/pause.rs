@@ -0,0 +1,71 @@
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static PAUSE: Lazy<Mutex<Pause>> = Lazy::new(|| Mutex::new(Pause::new()));
+
+// Whether clipboard capture is paused, e.g. while screen sharing.
+// This isn't saved, ClipStash always starts capturing.
+pub struct Pause {
+    paused: bool,
+    // Set if the pause ends by itself
+    resume_at: Option<Instant>,
+}
+
+impl Pause {
+    fn new() -> Self {
+        Self {
+            paused: false,
+            resume_at: None,
+        }
+    }
+
+    pub fn get_instance() -> std::sync::MutexGuard<'static, Pause> {
+        PAUSE.lock().unwrap()
+    }
+
+    // Pauses until resumed, or for the given duration
+    pub fn pause(&mut self, duration: Option<Duration>) {
+        self.paused = true;
+        self.resume_at = duration.map(|duration| Instant::now() + duration);
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.resume_at = None;
+    }
+
+    // Resumes first if the pause has run out
+    pub fn is_paused(&mut self) -> bool {
+        if self
+            .resume_at
+            .is_some_and(|resume_at| Instant::now() >= resume_at)
+        {
+            self.resume();
+        }
+        self.paused
+    }
+
+    // When a timed pause ends
+    pub fn resume_deadline(&self) -> Option<Instant> {
+        self.resume_at
+    }
+
+    // When a timed pause ends, in local time
+    pub fn resume_time(&self) -> Option<DateTime<Local>> {
+        let remaining = self.resume_at?.saturating_duration_since(Instant::now());
+        Some(Local::now() + chrono::Duration::from_std(remaining).ok()?)
+    }
+
+    // A short description for the window and command line, e.g. "Paused until 14:30"
+    pub fn describe(&mut self) -> String {
+        if !self.is_paused() {
+            return "Capturing".to_string();
+        }
+        match self.resume_time() {
+            Some(resume_time) => format!("Paused until {}", resume_time.format("%H:%M")),
+            None => "Paused".to_string(),
+        }
+    }
+}
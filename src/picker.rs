@@ -0,0 +1,101 @@
+use std::io::{self, BufRead, Write};
+use std::process::Stdio;
+
+use crate::cli::{self, Command};
+use crate::preferences::Config;
+
+// Picking works with any line based launcher (dmenu, rofi -dmenu, fzf...):
+// it's given one entry per line and prints back the line that was picked.
+pub fn run(args: &[String]) -> Result<String, String> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("--list") => entries(),
+        Some("--select") => {
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .map_err(|e| e.to_string())?;
+            select(&line)
+        }
+        Some(arg) => Err(format!("Unknown pick option: {}", arg)),
+        None => {
+            let launcher = Config::get_instance().get_picker_command();
+            if launcher.is_empty() {
+                return Err("No picker command is set, add one to the preferences as \
+                     \"picker_command\" (e.g. \"rofi -dmenu\"), or run \
+                     `clipstash pick --list | fzf | clipstash pick --select`"
+                    .to_string());
+            }
+
+            let selection = run_launcher(&launcher, entries()?)?;
+            select(&selection)
+        }
+    }
+}
+
+// Entries are "<id>\t<text>", with the text escaped to fit on one line.
+// Launchers show the first lines at the top, so the newest items come first.
+fn entries() -> Result<String, String> {
+    let list = cli::dispatch(&Command::List)?;
+    let entries = list
+        .lines()
+        .rev()
+        .map(|line| format!("{}\n", line))
+        .collect();
+    Ok(entries)
+}
+
+fn select(line: &str) -> Result<String, String> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line.is_empty() {
+        return Err("Nothing was picked".to_string());
+    }
+
+    let id = line.split('\t').next().unwrap_or_default();
+    let id = id
+        .parse()
+        .map_err(|_| format!("Invalid selection: {}", line))?;
+    cli::dispatch(&Command::Copy(id))
+}
+
+fn run_launcher(launcher: &str, entries: String) -> Result<String, String> {
+    let mut child = shell_command(launcher)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run picker command: {}", e))?;
+
+    // Write the entries from another thread, in case the launcher starts
+    // printing before it has read all of them
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(entries.as_bytes());
+    });
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run picker command: {}", e))?;
+    let _ = writer.join();
+
+    // Launchers exit with an error when the user cancels
+    if !output.status.success() {
+        return Err("Nothing was picked".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn shell_command(command: &str) -> std::process::Command {
+    #[cfg(unix)]
+    {
+        let mut process = std::process::Command::new("sh");
+        process.arg("-c").arg(command);
+        process
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let mut process = std::process::Command::new("cmd");
+        process.arg("/C").arg(command);
+        process
+    }
+}
@@ -0,0 +1,363 @@
+// Clipboard metadata that arboard doesn't expose, read straight from the platform
+
+pub struct ClipboardHints {
+    #[cfg(target_os = "linux")]
+    x11: Option<x11::SelectionTargets>,
+}
+
+impl ClipboardHints {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            x11: x11::SelectionTargets::new(),
+        }
+    }
+
+    // Password managers mark the secrets they copy so clipboard managers can treat
+    // them with care: KDE's Klipper convention on X11 is an extra
+    // `x-kde-passwordManagerHint` target, and Windows has a clipboard format named
+    // `ExcludeClipboardContentFromMonitorProcessing`.
+    pub fn is_marked_sensitive(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            self.x11
+                .as_ref()
+                .is_some_and(|x11| x11.contains("x-kde-passwordManagerHint"))
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            use winapi::um::winuser::{IsClipboardFormatAvailable, RegisterClipboardFormatW};
+
+            let name: Vec<u16> = "ExcludeClipboardContentFromMonitorProcessing\0"
+                .encode_utf16()
+                .collect();
+            // Registering returns the existing format if it's already registered
+            let format = unsafe { RegisterClipboardFormatW(name.as_ptr()) };
+            format != 0 && unsafe { IsClipboardFormatAvailable(format) } != 0
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        false
+    }
+
+    // Changes every time something is copied, without reading what was copied.
+    // None if it can't be told, e.g. when the owner doesn't say when it copied.
+    pub fn change_stamp(&self) -> Option<u64> {
+        #[cfg(target_os = "linux")]
+        {
+            self.x11
+                .as_ref()
+                .and_then(|x11| x11.timestamp())
+                .map(u64::from)
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            use winapi::um::winuser::GetClipboardSequenceNumber;
+
+            let sequence_number = unsafe { GetClipboardSequenceNumber() };
+            (sequence_number != 0).then_some(u64::from(sequence_number))
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        None
+    }
+
+    // Names of the application that owns the clipboard, i.e. copied its contents:
+    // its window class and process name, if they're known
+    pub fn owner_names(&self) -> Vec<String> {
+        #[cfg(target_os = "linux")]
+        {
+            self.x11
+                .as_ref()
+                .map(|x11| x11.owner_names())
+                .unwrap_or_default()
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            windows::clipboard_owner_names()
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        Vec::new()
+    }
+}
+
+// Where the mouse cursor is on the screen, in physical pixels
+pub fn cursor_position() -> Option<(i32, i32)> {
+    #[cfg(target_os = "linux")]
+    {
+        x11::cursor_position()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use winapi::shared::windef::POINT;
+        use winapi::um::winuser::GetCursorPos;
+
+        let mut point = POINT::default();
+        if unsafe { GetCursorPos(&mut point) } == 0 {
+            return None;
+        }
+        Some((point.x, point.y))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    None
+}
+
+// Opens a URL, a mailto: link or a folder with the default application
+pub fn open(target: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    let program = "xdg-open";
+    #[cfg(target_os = "windows")]
+    let program = "explorer";
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    let program = "open";
+
+    let mut child = std::process::Command::new(program)
+        .arg(target)
+        .spawn()
+        .map_err(|e| format!("Failed to open {}: {}", target, e))?;
+    // Waited for on a thread of its own, so it doesn't linger once it exits
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::path::Path;
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{GetClassNameW, GetClipboardOwner, GetWindowThreadProcessId};
+
+    pub fn clipboard_owner_names() -> Vec<String> {
+        let mut names = Vec::new();
+        let owner = unsafe { GetClipboardOwner() };
+        if owner.is_null() {
+            return names;
+        }
+
+        let mut class_name = [0u16; 256];
+        let length = unsafe { GetClassNameW(owner, class_name.as_mut_ptr(), 256) };
+        if length > 0 {
+            names.push(String::from_utf16_lossy(&class_name[..length as usize]));
+        }
+
+        let mut process_id: DWORD = 0;
+        unsafe { GetWindowThreadProcessId(owner, &mut process_id) };
+        if let Some(path) = process_image_path(process_id) {
+            // Both "KeePass" and "KeePass.exe" match
+            let path = Path::new(&path);
+            if let Some(file_stem) = path.file_stem() {
+                names.push(file_stem.to_string_lossy().into_owned());
+            }
+            if let Some(file_name) = path.file_name() {
+                names.push(file_name.to_string_lossy().into_owned());
+            }
+        }
+        names
+    }
+
+    fn process_image_path(process_id: DWORD) -> Option<String> {
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id) };
+        if process.is_null() {
+            return None;
+        }
+
+        let mut path = [0u16; 1024];
+        let mut length = path.len() as DWORD;
+        let result =
+            unsafe { QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut length) };
+        unsafe { CloseHandle(process) };
+
+        if result == 0 {
+            return None;
+        }
+        Some(String::from_utf16_lossy(&path[..length as usize]))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::time::{Duration, Instant};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt, CreateWindowAux, GetPropertyReply, Window, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+
+    // How long the clipboard owner has to answer before it's assumed there's no hint
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    // Asks the owner of the clipboard which formats (targets) it offers, or
+    // when it copied, through a hidden window that receives the answer
+    pub struct SelectionTargets {
+        connection: RustConnection,
+        window: Window,
+        clipboard: Atom,
+        targets: Atom,
+        timestamp: Atom,
+        property: Atom,
+    }
+
+    impl SelectionTargets {
+        // None if there's no X server to connect to, e.g. on a pure Wayland session
+        pub fn new() -> Option<Self> {
+            let (connection, screen_num) = x11rb::connect(None).ok()?;
+            let screen = &connection.setup().roots[screen_num];
+            let window = connection.generate_id().ok()?;
+            connection
+                .create_window(
+                    COPY_DEPTH_FROM_PARENT,
+                    window,
+                    screen.root,
+                    0,
+                    0,
+                    1,
+                    1,
+                    0,
+                    WindowClass::INPUT_ONLY,
+                    screen.root_visual,
+                    &CreateWindowAux::new(),
+                )
+                .ok()?;
+
+            let clipboard = intern_atom(&connection, "CLIPBOARD")?;
+            let targets = intern_atom(&connection, "TARGETS")?;
+            let timestamp = intern_atom(&connection, "TIMESTAMP")?;
+            let property = intern_atom(&connection, "CLIPSTASH_TARGETS")?;
+            Some(Self {
+                connection,
+                window,
+                clipboard,
+                targets,
+                timestamp,
+                property,
+            })
+        }
+
+        // The WM_CLASS of the window owning the clipboard, and the name of its process
+        pub fn owner_names(&self) -> Vec<String> {
+            let mut names = Vec::new();
+            let Some(owner) = self
+                .connection
+                .get_selection_owner(self.clipboard)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .map(|reply| reply.owner)
+                .filter(|&owner| owner != NONE)
+            else {
+                return names;
+            };
+
+            // WM_CLASS holds the instance and class names, NUL separated
+            if let Some(wm_class) = self.get_window_property(owner, AtomEnum::WM_CLASS.into()) {
+                names.extend(
+                    wm_class
+                        .split(|&byte| byte == 0)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).into_owned()),
+                );
+            }
+
+            let pid = intern_atom(&self.connection, "_NET_WM_PID")
+                .and_then(|atom| self.get_window_property(owner, atom))
+                .filter(|pid| pid.len() == 4)
+                .map(|pid| u32::from_ne_bytes([pid[0], pid[1], pid[2], pid[3]]));
+            if let Some(pid) = pid {
+                if let Ok(comm) = std::fs::read_to_string(format!("/proc/{}/comm", pid)) {
+                    names.push(comm.trim().to_string());
+                }
+            }
+            names
+        }
+
+        fn get_window_property(&self, window: Window, property: Atom) -> Option<Vec<u8>> {
+            let reply = self
+                .connection
+                .get_property(false, window, property, AtomEnum::ANY, 0, 256)
+                .ok()?
+                .reply()
+                .ok()?;
+            (!reply.value.is_empty()).then_some(reply.value)
+        }
+
+        pub fn contains(&self, target: &str) -> bool {
+            let Some(target) = intern_atom(&self.connection, target) else {
+                return false;
+            };
+            self.get().is_some_and(|targets| targets.contains(&target))
+        }
+
+        // The server time the owner took the clipboard at, set again by every copy
+        pub fn timestamp(&self) -> Option<u32> {
+            self.convert(self.timestamp)?.value32()?.next()
+        }
+
+        fn get(&self) -> Option<Vec<Atom>> {
+            let targets = self.convert(self.targets)?.value32()?.collect();
+            Some(targets)
+        }
+
+        fn convert(&self, target: Atom) -> Option<GetPropertyReply> {
+            self.connection
+                .convert_selection(
+                    self.window,
+                    self.clipboard,
+                    target,
+                    self.property,
+                    CURRENT_TIME,
+                )
+                .ok()?;
+            self.connection.flush().ok()?;
+
+            let deadline = Instant::now() + TIMEOUT;
+            loop {
+                match self.connection.poll_for_event().ok()? {
+                    Some(Event::SelectionNotify(event)) if event.requestor == self.window => {
+                        // The owner refused to convert the selection
+                        if event.property == NONE {
+                            return None;
+                        }
+                        break;
+                    }
+                    Some(_) => continue,
+                    None if Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(5))
+                    }
+                    None => return None,
+                }
+            }
+
+            self.connection
+                .get_property(true, self.window, self.property, AtomEnum::ANY, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()
+        }
+    }
+
+    fn intern_atom(connection: &RustConnection, name: &str) -> Option<Atom> {
+        let reply = connection
+            .intern_atom(false, name.as_bytes())
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.atom)
+    }
+
+    pub fn cursor_position() -> Option<(i32, i32)> {
+        let (connection, screen_num) = x11rb::connect(None).ok()?;
+        let root = connection.setup().roots[screen_num].root;
+        let reply = connection.query_pointer(root).ok()?.reply().ok()?;
+        Some((reply.root_x as i32, reply.root_y as i32))
+    }
+}
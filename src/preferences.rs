@@ -1,16 +1,76 @@
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::ErrorKind;
 use std::sync::Mutex;
 
+use crate::ignore::IgnoreRule;
+use crate::keymap::Keymap;
+use crate::paste::PasteChord;
+use crate::table::{Column, TableSort};
+
 static CONFIG: Lazy<Mutex<Config>> = Lazy::new(|| Mutex::new(Config::new()));
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     trim_clips: bool,
     save_history: bool,
+    // Launcher used by `clipstash pick`, e.g. "rofi -dmenu" or "fzf"
+    #[serde(default)]
+    picker_command: String,
+    // Seconds after which sensitive clips are removed from the history, 0 to keep them
+    #[serde(default = "default_sensitive_clip_lifetime")]
+    sensitive_clip_lifetime: u64,
+    // Clipboard content matching any of these is never captured
+    #[serde(default)]
+    ignore_rules: Vec<IgnoreRule>,
+    // Argon2 hash of the passphrase that unlocks the window, empty if there's none
+    #[serde(default)]
+    lock_passphrase_hash: String,
+    // Minutes of inactivity after which the window locks, 0 to only lock on demand
+    #[serde(default)]
+    lock_after_minutes: u64,
+    // Put between the texts of the selected items when they're copied together
+    #[serde(default = "default_join_separator")]
+    join_separator: String,
+    // Keyboard shortcuts that differ from the defaults, e.g. { "down": ["Down", "J"] }
+    #[serde(default)]
+    keybindings: BTreeMap<String, Vec<String>>,
+    // Why the keybindings above can't be used, the defaults are used instead
+    #[serde(skip)]
+    keybindings_error: Option<String>,
+    // Shows and hides the window from anywhere on X11, e.g. "Super+V", empty to disable it
+    #[serde(default = "default_global_hotkey")]
+    global_hotkey: String,
+    // Whether the global hotkey opens the compact popup instead of the full window
+    #[serde(default)]
+    hotkey_opens_popup: bool,
+    // How many of the latest clips the compact popup lists
+    #[serde(default = "default_popup_items")]
+    popup_items: usize,
+    // Whether picking a clip also pastes it into the window that was active before
+    #[serde(default)]
+    auto_paste: bool,
+    #[serde(default)]
+    paste_chord: PasteChord,
+    // The pane next to the list that shows the whole selected clip
+    #[serde(default = "default_show_preview")]
+    show_preview: bool,
+    #[serde(default = "default_preview_width")]
+    preview_width: f32,
+    #[serde(default)]
+    preview_word_wrap: bool,
+    #[serde(default = "default_preview_highlight")]
+    preview_highlight: bool,
+    // Columns of the history table the user has hidden or resized, and how it's sorted
+    #[serde(default)]
+    hidden_columns: Vec<Column>,
+    #[serde(default)]
+    column_widths: BTreeMap<Column, f32>,
+    #[serde(default)]
+    table_sort: Option<TableSort>,
     config_file_path: String,
 }
 
@@ -35,44 +95,68 @@ impl Config {
             Ok(data) => {
                 match serde_json::from_slice::<Config>(&data) {
                     Ok(config) => {
-                        let trim_clips = config.trim_clips;
-                        let save_history = config.save_history;
-
-                        Self {
-                            trim_clips,
-                            save_history,
+                        let mut config = Self {
                             config_file_path: file_path,
+                            ..config
+                        };
+                        // Bad keybindings only fall back to the defaults, instead of
+                        // the whole file
+                        if let Err(e) = Keymap::from_config(&config.keybindings) {
+                            eprintln!("Invalid keybindings in preferences.json: {}", e);
+                            config.keybindings_error = Some(e);
                         }
+                        config.ignore_rules.iter_mut().for_each(IgnoreRule::compile);
+                        config
                     }
                     Err(_) => {
                         // If the file exists but is not valid JSON,
-                        Self {
-                            trim_clips: true,
-                            save_history: true,
-                            config_file_path: file_path,
-                        }
+                        Self::defaults(file_path)
                     }
                 }
             }
             Err(ref e) if e.kind() == ErrorKind::NotFound => {
                 // If the file doesn't exist,
-                Self {
-                    trim_clips: true,
-                    save_history: true,
-                    config_file_path: file_path,
-                }
+                Self::defaults(file_path)
             }
             Err(_) => {
                 // If there's another kind of error,
                 Self {
                     trim_clips: false,
                     save_history: false,
-                    config_file_path: file_path,
+                    ..Self::defaults(file_path)
                 }
             }
         }
     }
 
+    pub fn defaults(config_file_path: String) -> Self {
+        Self {
+            trim_clips: true,
+            save_history: true,
+            picker_command: String::new(),
+            sensitive_clip_lifetime: default_sensitive_clip_lifetime(),
+            ignore_rules: Vec::new(),
+            lock_passphrase_hash: String::new(),
+            lock_after_minutes: 0,
+            join_separator: default_join_separator(),
+            keybindings: BTreeMap::new(),
+            keybindings_error: None,
+            global_hotkey: default_global_hotkey(),
+            hotkey_opens_popup: false,
+            popup_items: default_popup_items(),
+            auto_paste: false,
+            paste_chord: PasteChord::default(),
+            show_preview: default_show_preview(),
+            preview_width: default_preview_width(),
+            preview_word_wrap: false,
+            preview_highlight: default_preview_highlight(),
+            hidden_columns: Vec::new(),
+            column_widths: BTreeMap::new(),
+            table_sort: None,
+            config_file_path,
+        }
+    }
+
     pub fn get_instance() -> std::sync::MutexGuard<'static, Config> {
         CONFIG.lock().unwrap()
     }
@@ -97,9 +181,225 @@ impl Config {
             .expect("[set_trim_clips] Failed to save config to file");
     }
 
+    pub fn get_picker_command(&self) -> String {
+        self.picker_command.clone()
+    }
+
+    pub fn get_sensitive_clip_lifetime(&self) -> u64 {
+        self.sensitive_clip_lifetime
+    }
+
+    pub fn set_sensitive_clip_lifetime(&mut self, sensitive_clip_lifetime: u64) {
+        self.sensitive_clip_lifetime = sensitive_clip_lifetime;
+        self.save()
+            .expect("[set_sensitive_clip_lifetime] Failed to save config to file");
+    }
+
+    pub fn get_ignore_rules(&self) -> Vec<IgnoreRule> {
+        self.ignore_rules.clone()
+    }
+
+    pub fn set_ignore_rules(&mut self, mut ignore_rules: Vec<IgnoreRule>) {
+        ignore_rules.iter_mut().for_each(IgnoreRule::compile);
+        self.ignore_rules = ignore_rules;
+        self.save()
+            .expect("[set_ignore_rules] Failed to save config to file");
+    }
+
+    pub fn has_lock_passphrase(&self) -> bool {
+        !self.lock_passphrase_hash.is_empty()
+    }
+
+    pub fn get_lock_passphrase_hash(&self) -> String {
+        self.lock_passphrase_hash.clone()
+    }
+
+    // An empty hash removes the lock, along with locking when idle
+    pub fn set_lock_passphrase_hash(&mut self, lock_passphrase_hash: String) {
+        if lock_passphrase_hash.is_empty() {
+            self.lock_after_minutes = 0;
+        }
+        self.lock_passphrase_hash = lock_passphrase_hash;
+        self.save()
+            .expect("[set_lock_passphrase_hash] Failed to save config to file");
+    }
+
+    pub fn get_lock_after_minutes(&self) -> u64 {
+        self.lock_after_minutes
+    }
+
+    pub fn set_lock_after_minutes(&mut self, lock_after_minutes: u64) {
+        self.lock_after_minutes = lock_after_minutes;
+        self.save()
+            .expect("[set_lock_after_minutes] Failed to save config to file");
+    }
+
+    pub fn get_join_separator(&self) -> String {
+        self.join_separator.clone()
+    }
+
+    pub fn set_join_separator(&mut self, join_separator: &str) {
+        self.join_separator = join_separator.to_string();
+        self.save()
+            .expect("[set_join_separator] Failed to save config to file");
+    }
+
+    // The keybindings, or the defaults if they're invalid
+    pub fn get_keymap(&self) -> Keymap {
+        Keymap::from_config(&self.keybindings).unwrap_or_default()
+    }
+
+    pub fn get_keybindings_error(&self) -> Option<String> {
+        self.keybindings_error.clone()
+    }
+
+    pub fn set_keymap(&mut self, keymap: &Keymap) {
+        self.keybindings = keymap.to_config();
+        self.keybindings_error = None;
+        self.save()
+            .expect("[set_keymap] Failed to save config to file");
+    }
+
+    pub fn get_global_hotkey(&self) -> String {
+        self.global_hotkey.clone()
+    }
+
+    pub fn set_global_hotkey(&mut self, global_hotkey: &str) {
+        self.global_hotkey = global_hotkey.to_string();
+        self.save()
+            .expect("[set_global_hotkey] Failed to save config to file");
+    }
+
+    pub fn get_hotkey_opens_popup(&self) -> bool {
+        self.hotkey_opens_popup
+    }
+
+    pub fn set_hotkey_opens_popup(&mut self, hotkey_opens_popup: bool) {
+        self.hotkey_opens_popup = hotkey_opens_popup;
+        self.save()
+            .expect("[set_hotkey_opens_popup] Failed to save config to file");
+    }
+
+    pub fn get_popup_items(&self) -> usize {
+        self.popup_items
+    }
+
+    // The keys to paste with, if picking a clip pastes it
+    pub fn get_auto_paste(&self) -> Option<PasteChord> {
+        self.auto_paste.then_some(self.paste_chord)
+    }
+
+    pub fn set_auto_paste(&mut self, auto_paste: Option<PasteChord>) {
+        self.auto_paste = auto_paste.is_some();
+        if let Some(paste_chord) = auto_paste {
+            self.paste_chord = paste_chord;
+        }
+        self.save()
+            .expect("[set_auto_paste] Failed to save config to file");
+    }
+
+    pub fn get_show_preview(&self) -> bool {
+        self.show_preview
+    }
+
+    pub fn set_show_preview(&mut self, show_preview: bool) {
+        self.show_preview = show_preview;
+        self.save()
+            .expect("[set_show_preview] Failed to save config to file");
+    }
+
+    pub fn get_preview_width(&self) -> f32 {
+        self.preview_width
+    }
+
+    pub fn set_preview_width(&mut self, preview_width: f32) {
+        self.preview_width = preview_width;
+        self.save()
+            .expect("[set_preview_width] Failed to save config to file");
+    }
+
+    pub fn get_preview_word_wrap(&self) -> bool {
+        self.preview_word_wrap
+    }
+
+    pub fn set_preview_word_wrap(&mut self, preview_word_wrap: bool) {
+        self.preview_word_wrap = preview_word_wrap;
+        self.save()
+            .expect("[set_preview_word_wrap] Failed to save config to file");
+    }
+
+    pub fn get_preview_highlight(&self) -> bool {
+        self.preview_highlight
+    }
+
+    pub fn set_preview_highlight(&mut self, preview_highlight: bool) {
+        self.preview_highlight = preview_highlight;
+        self.save()
+            .expect("[set_preview_highlight] Failed to save config to file");
+    }
+
+    pub fn get_hidden_columns(&self) -> Vec<Column> {
+        self.hidden_columns.clone()
+    }
+
+    pub fn set_hidden_columns(&mut self, hidden_columns: Vec<Column>) {
+        self.hidden_columns = hidden_columns;
+        self.save()
+            .expect("[set_hidden_columns] Failed to save config to file");
+    }
+
+    pub fn get_column_widths(&self) -> BTreeMap<Column, f32> {
+        self.column_widths.clone()
+    }
+
+    pub fn set_column_widths(&mut self, column_widths: BTreeMap<Column, f32>) {
+        self.column_widths = column_widths;
+        self.save()
+            .expect("[set_column_widths] Failed to save config to file");
+    }
+
+    pub fn get_table_sort(&self) -> Option<TableSort> {
+        self.table_sort
+    }
+
+    pub fn set_table_sort(&mut self, table_sort: Option<TableSort>) {
+        self.table_sort = table_sort;
+        self.save()
+            .expect("[set_table_sort] Failed to save config to file");
+    }
+
     fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
         let data = serde_json::to_vec(self)?;
         fs::write(&self.config_file_path, data)?;
         Ok(())
     }
 }
+
+fn default_sensitive_clip_lifetime() -> u64 {
+    5 * 60
+}
+
+fn default_join_separator() -> String {
+    "\n".to_string()
+}
+
+// Off until one is chosen, desktops like GNOME already use Super+V
+fn default_global_hotkey() -> String {
+    String::new()
+}
+
+fn default_popup_items() -> usize {
+    10
+}
+
+fn default_show_preview() -> bool {
+    true
+}
+
+fn default_preview_width() -> f32 {
+    320.0
+}
+
+fn default_preview_highlight() -> bool {
+    true
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use crate::content::ContentType;
+use crate::history::ClipboardItem;
+
+// The columns of the history table, in the order they're shown
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Content,
+    Kind,
+    Size,
+    Copied,
+    CopyCount,
+}
+
+impl Column {
+    pub const ALL: [Column; 5] = [
+        Column::Content,
+        Column::Kind,
+        Column::Size,
+        Column::Copied,
+        Column::CopyCount,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Column::Content => "Content",
+            Column::Kind => "Kind",
+            Column::Size => "Size",
+            Column::Copied => "Copied",
+            Column::CopyCount => "Copies",
+        }
+    }
+
+    // The width it starts with, the content takes up whatever is left
+    pub fn default_width(&self) -> f32 {
+        match self {
+            Column::Content => 0.0,
+            Column::Kind => 50.0,
+            Column::Size => 70.0,
+            Column::Copied => 120.0,
+            Column::CopyCount => 50.0,
+        }
+    }
+}
+
+// Without one the table is in history order, oldest first
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TableSort {
+    pub column: Column,
+    pub descending: bool,
+}
+
+impl TableSort {
+    pub fn compare(&self, a: &ClipboardItem, b: &ClipboardItem) -> Ordering {
+        let ordering = match self.column {
+            // Sensitive items sort by their masked text, which gives nothing away
+            Column::Content => a
+                .display_text()
+                .to_lowercase()
+                .cmp(&b.display_text().to_lowercase()),
+            Column::Kind => kind_label(a).cmp(kind_label(b)),
+            Column::Size => size_of(a).cmp(&size_of(b)),
+            Column::Copied => a.created_at.cmp(&b.created_at),
+            Column::CopyCount => a.copy_count.cmp(&b.copy_count),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+// Sensitive items sort as text, so their place doesn't tell what they are
+fn kind_label(item: &ClipboardItem) -> &'static str {
+    if item.sensitive {
+        ContentType::Text.label()
+    } else {
+        item.content_type.label()
+    }
+}
+
+// Sensitive items sort before the others, all as the same size
+fn size_of(item: &ClipboardItem) -> Option<usize> {
+    (!item.sensitive).then(|| item.byte_count())
+}
+
+// e.g. "512 B" or "1.5 KB"
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(text: &str, copy_count: u32) -> ClipboardItem {
+        let mut item = ClipboardItem::new(0, text.to_string());
+        item.copy_count = copy_count;
+        item
+    }
+
+    #[test]
+    fn items_sort_by_column() {
+        let items = [item("bb", 1), item("a", 3), item("ccc", 1)];
+        let sorted = |sort: TableSort| {
+            let mut sorted: Vec<&ClipboardItem> = items.iter().collect();
+            sorted.sort_by(|a, b| sort.compare(a, b));
+            sorted
+                .iter()
+                .map(|item| item.text.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        let by_content = TableSort {
+            column: Column::Content,
+            descending: false,
+        };
+        assert_eq!(sorted(by_content), ["a", "bb", "ccc"]);
+        let by_size = TableSort {
+            column: Column::Size,
+            descending: true,
+        };
+        assert_eq!(sorted(by_size), ["ccc", "bb", "a"]);
+        // Ties keep the history order
+        let by_copies = TableSort {
+            column: Column::CopyCount,
+            descending: false,
+        };
+        assert_eq!(sorted(by_copies), ["bb", "ccc", "a"]);
+    }
+
+    #[test]
+    fn sensitive_items_sort_by_kind_as_text() {
+        let mut items = [
+            item("https://example.com", 0),
+            item("plain", 0),
+            item("https://example.com/secret", 0),
+        ];
+        items[2].sensitive = true;
+        let by_kind = TableSort {
+            column: Column::Kind,
+            descending: false,
+        };
+        let mut sorted: Vec<&ClipboardItem> = items.iter().collect();
+        sorted.sort_by(|a, b| by_kind.compare(a, b));
+        let texts: Vec<&str> = sorted.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["plain", "https://example.com/secret", "https://example.com"]
+        );
+    }
+
+    #[test]
+    fn sensitive_items_sort_by_size_together() {
+        let mut items = [
+            item("bb", 0),
+            item("a much longer secret", 0),
+            item("a", 0),
+            item("c", 0),
+        ];
+        items[1].sensitive = true;
+        items[3].sensitive = true;
+        let by_size = TableSort {
+            column: Column::Size,
+            descending: false,
+        };
+        let mut sorted: Vec<&ClipboardItem> = items.iter().collect();
+        sorted.sort_by(|a, b| by_size.compare(a, b));
+        let texts: Vec<&str> = sorted.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(texts, ["a much longer secret", "c", "a", "bb"]);
+    }
+
+    #[test]
+    fn sizes_are_formatted() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
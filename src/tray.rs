@@ -0,0 +1,337 @@
+// The tray icon and its menu. systray only hands its Application to the menu
+// callbacks, so the icon couldn't be changed by the event loop, e.g. when
+// capture is paused from the window or the command line, or a timed pause ends.
+// On Linux the tray is built with GTK and on Windows with the notification area
+// API directly, elsewhere systray is kept and the icon isn't changed.
+
+type Callback = Box<dyn Fn() + Send + Sync>;
+
+pub struct Tray {
+    icon_path: String,
+    items: Vec<(String, Callback)>,
+}
+
+impl Tray {
+    pub fn new(icon_path: &str) -> Self {
+        Self {
+            icon_path: icon_path.to_string(),
+            items: Vec::new(),
+        }
+    }
+
+    // The callback runs on the tray's own thread
+    pub fn add_item<F>(&mut self, label: &str, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.items.push((label.to_string(), Box::new(callback)));
+    }
+
+    // Shows the icon and runs the menu on a thread of its own
+    pub fn show(self) -> Result<(), String> {
+        imp::show(self.icon_path, self.items)
+    }
+}
+
+// Can be called from any thread, once the tray is shown
+pub fn set_icon(icon_path: &str) {
+    imp::set_icon(icon_path);
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use gtk::prelude::*;
+    use libappindicator::{AppIndicator, AppIndicatorStatus};
+    use std::cell::RefCell;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::Callback;
+
+    // GTK objects can only be used on the thread GTK was initialized on
+    thread_local!(static INDICATOR: RefCell<Option<AppIndicator>> = const { RefCell::new(None) });
+
+    pub fn show(icon_path: String, items: Vec<(String, Callback)>) -> Result<(), String> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            if gtk::init().is_err() {
+                let _ = tx.send(Err("Failed to initialize GTK".to_string()));
+                return;
+            }
+
+            let mut menu = gtk::Menu::new();
+            for (label, callback) in items {
+                let item = gtk::MenuItem::new_with_label(&label);
+                item.connect_activate(move |_| callback());
+                menu.append(&item);
+            }
+            menu.show_all();
+
+            let mut indicator = AppIndicator::new("clipstash", "");
+            indicator.set_status(AppIndicatorStatus::Active);
+            indicator.set_menu(&mut menu);
+            indicator.set_icon_full(&icon_path, "icon");
+            INDICATOR.with(|cell| *cell.borrow_mut() = Some(indicator));
+
+            let _ = tx.send(Ok(()));
+            gtk::main();
+        });
+        rx.recv()
+            .map_err(|_| "The tray thread exited".to_string())?
+    }
+
+    pub fn set_icon(icon_path: &str) {
+        let icon_path = icon_path.to_string();
+        // Runs the closure on the GTK thread
+        glib::idle_add(move || {
+            INDICATOR.with(|cell| {
+                if let Some(indicator) = cell.borrow_mut().as_mut() {
+                    indicator.set_icon_full(&icon_path, "icon");
+                }
+            });
+            glib::Continue(false)
+        });
+    }
+}
+
+// The icon of the window in the notification area, owned here so it can be
+// changed and the icon it replaces destroyed
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::cell::RefCell;
+    use std::os::windows::ffi::OsStrExt;
+    use std::sync::{mpsc, Mutex};
+    use std::thread;
+    use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+    use winapi::shared::windef::{HICON, HWND, POINT};
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::shellapi::{
+        Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_MODIFY, NOTIFYICONDATAW,
+    };
+    use winapi::um::winuser::{
+        AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyIcon, DestroyMenu,
+        DispatchMessageW, GetCursorPos, GetMessageW, LoadImageW, PostMessageW, RegisterClassW,
+        SetForegroundWindow, TrackPopupMenu, TranslateMessage, IMAGE_ICON, LR_DEFAULTSIZE,
+        LR_LOADFROMFILE, MF_STRING, TPM_NONOTIFY, TPM_RETURNCMD, WM_APP, WM_LBUTTONUP, WM_NULL,
+        WM_RBUTTONUP, WNDCLASSW,
+    };
+
+    use super::Callback;
+
+    // What the shell sends the window when the icon is clicked
+    const WM_TRAY_ICON: UINT = WM_APP + 1;
+    const ICON_ID: UINT = 1;
+
+    struct Icon {
+        window: HWND,
+        icon: HICON,
+    }
+
+    // The handles are only used through the shell, which any thread can call
+    unsafe impl Send for Icon {}
+
+    static ICON: Mutex<Option<Icon>> = Mutex::new(None);
+
+    // The menu items, for the window procedure on the tray's thread
+    thread_local!(static ITEMS: RefCell<Vec<(String, Callback)>> = const { RefCell::new(Vec::new()) });
+
+    fn wide(text: &str) -> Vec<u16> {
+        std::ffi::OsStr::new(text)
+            .encode_wide()
+            .chain(Some(0))
+            .collect()
+    }
+
+    fn load_icon(icon_path: &str) -> Result<HICON, String> {
+        let path = wide(icon_path);
+        let icon = unsafe {
+            LoadImageW(
+                std::ptr::null_mut(),
+                path.as_ptr(),
+                IMAGE_ICON,
+                0,
+                0,
+                LR_LOADFROMFILE | LR_DEFAULTSIZE,
+            )
+        };
+        if icon.is_null() {
+            return Err(format!("Failed to load the tray icon {}", icon_path));
+        }
+        Ok(icon as HICON)
+    }
+
+    fn notify_icon_data(window: HWND, flags: UINT, icon: HICON) -> NOTIFYICONDATAW {
+        NOTIFYICONDATAW {
+            cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+            hWnd: window,
+            uID: ICON_ID,
+            uFlags: flags,
+            hIcon: icon,
+            uCallbackMessage: WM_TRAY_ICON,
+            ..Default::default()
+        }
+    }
+
+    pub fn show(icon_path: String, items: Vec<(String, Callback)>) -> Result<(), String> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // The window gets its messages on the thread that created it
+            ITEMS.with(|cell| *cell.borrow_mut() = items);
+            let result = unsafe { add_icon(&icon_path) };
+            let added = result.is_ok();
+            let _ = tx.send(result);
+            if !added {
+                return;
+            }
+
+            unsafe {
+                let mut message = std::mem::zeroed();
+                while GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) > 0 {
+                    TranslateMessage(&message);
+                    DispatchMessageW(&message);
+                }
+            }
+        });
+        rx.recv()
+            .map_err(|_| "The tray thread exited".to_string())?
+    }
+
+    unsafe fn add_icon(icon_path: &str) -> Result<(), String> {
+        let class_name = wide("ClipStashTray");
+        let instance = GetModuleHandleW(std::ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: class_name.as_ptr(),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err("Failed to register the tray window class".to_string());
+        }
+        // Never shown, it only receives the clicks on the icon
+        let window = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            instance,
+            std::ptr::null_mut(),
+        );
+        if window.is_null() {
+            return Err("Failed to create the tray window".to_string());
+        }
+
+        let icon = load_icon(icon_path)?;
+        let mut data = notify_icon_data(window, NIF_ICON | NIF_MESSAGE | NIF_TIP, icon);
+        for (tip, c) in data.szTip.iter_mut().zip("ClipStash".encode_utf16()) {
+            *tip = c;
+        }
+        if Shell_NotifyIconW(NIM_ADD, &mut data) == 0 {
+            DestroyIcon(icon);
+            return Err("Failed to add the tray icon".to_string());
+        }
+        *ICON.lock().unwrap() = Some(Icon { window, icon });
+        Ok(())
+    }
+
+    unsafe extern "system" fn window_proc(
+        window: HWND,
+        message: UINT,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if message == WM_TRAY_ICON && matches!(lparam as UINT, WM_LBUTTONUP | WM_RBUTTONUP) {
+            show_menu(window);
+            return 0;
+        }
+        DefWindowProcW(window, message, wparam, lparam)
+    }
+
+    // Shows the menu at the cursor and runs the callback of the item picked
+    unsafe fn show_menu(window: HWND) {
+        let menu = CreatePopupMenu();
+        ITEMS.with(|items| {
+            for (i, (label, _)) in items.borrow().iter().enumerate() {
+                AppendMenuW(menu, MF_STRING, i + 1, wide(label).as_ptr());
+            }
+        });
+
+        let mut cursor = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut cursor);
+        // Otherwise the menu stays open when clicking elsewhere
+        SetForegroundWindow(window);
+        let picked = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_NONOTIFY,
+            cursor.x,
+            cursor.y,
+            0,
+            window,
+            std::ptr::null(),
+        );
+        PostMessageW(window, WM_NULL, 0, 0);
+        DestroyMenu(menu);
+
+        if picked > 0 {
+            ITEMS.with(|items| {
+                if let Some((_, callback)) = items.borrow().get(picked as usize - 1) {
+                    callback();
+                }
+            });
+        }
+    }
+
+    pub fn set_icon(icon_path: &str) {
+        let mut shown = ICON.lock().unwrap();
+        let Some(shown) = shown.as_mut() else {
+            return;
+        };
+        let Ok(icon) = load_icon(icon_path) else {
+            return;
+        };
+        unsafe {
+            let mut data = notify_icon_data(shown.window, NIF_ICON, icon);
+            if Shell_NotifyIconW(NIM_MODIFY, &mut data) == 0 {
+                DestroyIcon(icon);
+                return;
+            }
+            // The shell keeps a copy of its own
+            DestroyIcon(std::mem::replace(&mut shown.icon, icon));
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod imp {
+    use std::thread;
+    use systray::Application;
+
+    use super::Callback;
+
+    pub fn show(icon_path: String, items: Vec<(String, Callback)>) -> Result<(), String> {
+        let mut app = Application::new().map_err(|e| e.to_string())?;
+        app.set_icon_from_file(&icon_path)
+            .map_err(|e| e.to_string())?;
+        for (label, callback) in items {
+            let _ = app.add_menu_item(&label, move |_| {
+                callback();
+                Ok::<_, systray::Error>(())
+            });
+        }
+
+        thread::spawn(move || {
+            app.wait_for_message()
+                .expect_err("Failed to wait for system tray message");
+        });
+        Ok(())
+    }
+
+    // systray can only change the icon from its menu callbacks
+    pub fn set_icon(_icon_path: &str) {}
+}
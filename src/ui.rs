@@ -1,22 +1,349 @@
 use arboard::Clipboard;
+use chrono::{Local, TimeZone};
+use glium::glutin::event::VirtualKeyCode;
 use glium::glutin::event_loop::ControlFlow;
-use glium::Surface;
-use imgui::{Condition, Ui};
+use glium::glutin::window::Window;
+use glium::{Display, Surface};
+use imgui::{Condition, TextureId, Ui};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::WinitPlatform;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
 
-use crate::history::ClipboardHistory;
+use crate::content::{self, ContentType};
+use crate::crypto;
+use crate::export::{self, ExportFilter, ExportFormat};
+use crate::highlight::{self, Highlight, Theme, Token};
+use crate::history::{ClipboardHistory, ClipboardItem, MASKED_TEXT};
+use crate::ignore::{IgnoreRule, IgnoreRuleKind};
+use crate::keymap::{Action, KeyChord, Keymap};
+use crate::paste::{self, PasteChord};
+use crate::pause::Pause;
+use crate::platform;
 use crate::preferences::Config;
+use crate::table::{self, Column, TableSort};
+use crate::window::WINDOW_TITLE;
+use crate::window_lock::WindowLock;
+
+// Choices for how long capture is paused, in minutes
+const PAUSE_DURATIONS: [(&str, u64); 4] = [
+    ("5 Minutes", 5),
+    ("15 Minutes", 15),
+    ("30 Minutes", 30),
+    ("1 Hour", 60),
+];
+
+// Choices for how long sensitive clips are kept, in seconds
+const SENSITIVE_CLIP_LIFETIMES: [(&str, u64); 5] = [
+    ("30 Seconds", 30),
+    ("1 Minute", 60),
+    ("5 Minutes", 5 * 60),
+    ("1 Hour", 60 * 60),
+    ("Never", 0),
+];
+
+// Choices for what's put between the selected items when they're copied together
+const JOIN_SEPARATORS: [(&str, &str); 5] = [
+    ("New Line", "\n"),
+    ("Blank Line", "\n\n"),
+    ("Space", " "),
+    ("Comma", ", "),
+    ("Tab", "\t"),
+];
+
+// Choices for how long the window may be idle before it locks, in minutes
+const LOCK_AFTER_MINUTES: [(&str, u64); 5] = [
+    ("Never", 0),
+    ("1 Minute", 1),
+    ("5 Minutes", 5),
+    ("15 Minutes", 15),
+    ("30 Minutes", 30),
+];
+
+// How many items Page Up and Page Down move by
+const PAGE_SIZE: usize = 10;
+
+// Width of the handle between the list and the preview, and the narrowest either may get
+const SPLITTER_WIDTH: f32 = 6.0;
+const MIN_PANE_WIDTH: f32 = 150.0;
+
+// How long the toast offering to undo a change stays up
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+// Set from the event loop when the global hotkey shows the window
+static SEARCH_FOCUS_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Focuses the search box on the next frame
+pub fn request_search_focus() {
+    SEARCH_FOCUS_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+// Shown for a little while after a change that can be undone
+struct Toast {
+    message: String,
+    shown_at: Instant,
+}
+
+impl Toast {
+    fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            shown_at: Instant::now(),
+        }
+    }
+}
+
+struct ExportDialog {
+    open: bool,
+    format: ExportFormat,
+    pinned_only: bool,
+    // Only export the items selected when the dialog was opened
+    selected_only: bool,
+    selection: HashSet<u64>,
+    tag: String,
+    since: String,
+    until: String,
+    // Result of the last export, shown under the buttons
+    status: String,
+}
+
+impl Default for ExportDialog {
+    fn default() -> Self {
+        Self {
+            open: false,
+            format: ExportFormat::JsonLines,
+            pinned_only: false,
+            selected_only: false,
+            selection: HashSet::new(),
+            tag: String::new(),
+            since: String::new(),
+            until: String::new(),
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct EncryptDialog {
+    open: bool,
+    passphrase: String,
+    confirmation: String,
+    error: String,
+}
+
+impl EncryptDialog {
+    // Wipes the passphrases rather than just dropping them
+    fn close(&mut self) {
+        self.passphrase.zeroize();
+        self.confirmation.zeroize();
+        *self = Self::default();
+    }
+}
+
+// Edits the text of an item, which is looked up by id again when saved since
+// the history may change while the dialog is open
+#[derive(Default)]
+struct EditDialog {
+    open: bool,
+    id: u64,
+    text: String,
+    original_text: String,
+    revision_count: usize,
+    error: String,
+}
+
+impl EditDialog {
+    // The item may be sensitive, so its text is wiped
+    fn close(&mut self) {
+        self.text.zeroize();
+        self.original_text.zeroize();
+        *self = Self::default();
+    }
+}
+
+// Edits the shortcuts of each action as text, e.g. "Down, J"
+#[derive(Default)]
+struct KeybindingsDialog {
+    open: bool,
+    fields: Vec<(Action, String)>,
+    global_hotkey: String,
+    hotkey_opens_popup: bool,
+    error: String,
+}
+
+impl KeybindingsDialog {
+    fn fields_of(keymap: &Keymap) -> Vec<(Action, String)> {
+        Action::all()
+            .into_iter()
+            .map(|action| {
+                let labels: Vec<String> =
+                    keymap.chords(action).iter().map(KeyChord::label).collect();
+                (action, labels.join(", "))
+            })
+            .collect()
+    }
+
+    fn to_keymap(&self) -> Result<Keymap, String> {
+        let bindings = self
+            .fields
+            .iter()
+            .map(|(action, field)| {
+                let labels = field
+                    .split(',')
+                    .map(|label| label.trim().to_string())
+                    .filter(|label| !label.is_empty())
+                    .collect();
+                (action.name(), labels)
+            })
+            .collect();
+        Keymap::from_config(&bindings)
+    }
+}
+
+// Adds a tag to every selected item
+#[derive(Default)]
+struct TagDialog {
+    open: bool,
+    tag: String,
+}
+
+// Sets, changes or removes the lock passphrase, the current one is needed
+// to change or remove it
+#[derive(Default)]
+struct LockPassphraseDialog {
+    open: bool,
+    current: String,
+    passphrase: String,
+    confirmation: String,
+    error: String,
+}
+
+impl LockPassphraseDialog {
+    fn close(&mut self) {
+        self.current.zeroize();
+        self.passphrase.zeroize();
+        self.confirmation.zeroize();
+        *self = Self::default();
+    }
+}
+
+// Edits a copy of the ignore rules, which are only saved once they're all valid
+#[derive(Default)]
+struct IgnoreRulesDialog {
+    open: bool,
+    rules: Vec<IgnoreRule>,
+    error: String,
+}
+
+// The rows of the table, as indices into the history, which are only worked
+// out again when the history, the search or the sort changes
+#[derive(Default)]
+struct TableRows {
+    revision: Option<u64>,
+    query: String,
+    sort: Option<TableSort>,
+    indices: Vec<usize>,
+    ids: Vec<u64>,
+}
+
+// Picked from a row's context menu or by double-clicking it, and done once the
+// table is drawn, since the rows borrow the history
+enum RowAction {
+    Copy,
+    Pick,
+    // Opens the URL, email address or folder of the clip
+    Open,
+    PrettyPrintJson,
+    SetPinned(bool),
+    MoveUp,
+    MoveDown,
+    MergeWithNext,
+    SetSensitive(bool),
+    Remove,
+}
 
 pub struct UI {
     show_about_dialog: bool,
+    show_help_dialog: bool,
+    show_clear_dialog: bool,
+    toast: Option<Toast>,
+    export_dialog: ExportDialog,
+    encrypt_dialog: EncryptDialog,
+    edit_dialog: EditDialog,
+    keybindings_dialog: KeybindingsDialog,
+    tag_dialog: TagDialog,
+    lock_passphrase_dialog: LockPassphraseDialog,
+    ignore_rules_dialog: IgnoreRulesDialog,
+    unlock_passphrase: String,
+    unlock_error: String,
+    // Ids of the sensitive items whose text is shown
+    revealed_items: HashSet<u64>,
+    // Ids of the selected items, and of the one Shift-click selects from
+    selected_items: HashSet<u64>,
+    selection_anchor: Option<u64>,
+    // Set when the anchor is moved with the keyboard, so it's scrolled to
+    scroll_to_anchor: bool,
+    search_query: String,
+    // The search field is focused on the next frame, so the key that asked
+    // for it isn't typed into it
+    focus_search: bool,
+    // The search and highlighted row of the compact popup
+    popup_query: String,
+    popup_row: usize,
+    preview_width: f32,
+    // The previewed image's id and the texture it was uploaded to
+    preview_texture: Option<(u64, TextureId)>,
+    // The previewed text's id, edit count and length, and its highlighting
+    preview_highlight: Option<((u64, usize, usize), Highlight)>,
+    // imgui only takes the hidden columns and the sort into account when it creates the table
+    table_created: bool,
+    table_rows: Rc<TableRows>,
+    // Where the first row of the table is and how tall rows are, measured
+    // from the rows in view to scroll to the ones that aren't
+    row_metrics: Option<(f32, f32)>,
+    keymap: Keymap,
+    // Whether the window title currently says capture is paused
+    title_shows_paused: bool,
     auto_launch: auto_launch::AutoLaunch,
 }
 
 impl UI {
     pub fn new(autostart: auto_launch::AutoLaunch) -> Self {
+        // Locked once, a second lock in the same statement would wait on the first
+        let config = Config::get_instance();
         Self {
             show_about_dialog: false,
+            show_help_dialog: false,
+            show_clear_dialog: false,
+            toast: None,
+            export_dialog: ExportDialog::default(),
+            encrypt_dialog: EncryptDialog::default(),
+            edit_dialog: EditDialog::default(),
+            keybindings_dialog: KeybindingsDialog::default(),
+            tag_dialog: TagDialog::default(),
+            lock_passphrase_dialog: LockPassphraseDialog::default(),
+            ignore_rules_dialog: IgnoreRulesDialog::default(),
+            unlock_passphrase: String::new(),
+            unlock_error: String::new(),
+            revealed_items: HashSet::new(),
+            selected_items: HashSet::new(),
+            selection_anchor: None,
+            scroll_to_anchor: false,
+            search_query: String::new(),
+            focus_search: false,
+            popup_query: String::new(),
+            popup_row: 0,
+            preview_width: config.get_preview_width(),
+            preview_texture: None,
+            preview_highlight: None,
+            table_created: false,
+            table_rows: Rc::new(TableRows::default()),
+            row_metrics: None,
+            keymap: config.get_keymap(),
+            title_shows_paused: false,
             auto_launch: autostart,
         }
     }
@@ -37,6 +364,57 @@ impl UI {
 
         let mut open: bool = true;
 
+        // Lock before taking this frame's input into account, otherwise
+        // showing the window after a while would count as activity
+        let lock_after_minutes = Config::get_instance().get_lock_after_minutes();
+        let (window_locked, locked_now) = {
+            let mut window_lock = WindowLock::get_instance();
+            let locked_now = window_lock.lock_if_idle(lock_after_minutes);
+            let io = ui.io();
+            if io.mouse_delta != [0.0, 0.0]
+                || io.mouse_wheel != 0.0
+                || io.mouse_down.iter().any(|down| *down)
+                || io.keys_down.iter().any(|down| *down)
+            {
+                window_lock.record_activity();
+            }
+            (window_lock.is_locked(), locked_now)
+        };
+        if locked_now {
+            ClipboardHistory::get_instance().clear_undo();
+        }
+        if window_locked {
+            self.close_dialogs();
+        }
+
+        // Pausing is also visible from the taskbar, through the window title
+        let (paused, pause_status) = {
+            let mut pause = Pause::get_instance();
+            (pause.is_paused(), pause.describe())
+        };
+        if paused != self.title_shows_paused {
+            let title = if paused {
+                format!("{} (Paused)", WINDOW_TITLE)
+            } else {
+                WINDOW_TITLE.to_string()
+            };
+            gl_window.window().set_title(&title);
+            self.title_shows_paused = paused;
+        }
+
+        if crate::window::is_compact() {
+            let size = [size.width as f32, size.height as f32];
+            if self.draw_popup(&ui, gl_window.window(), size, window_locked) {
+                // Refresh the UI
+                *control_flow = ControlFlow::Poll;
+            }
+            render(ui, display, platform, renderer);
+            return;
+        }
+
+        // Set from the keyboard, the window is hidden once it's drawn
+        let mut hide_window = false;
+
         // Write code that creates a new window with a text saying "hello world" and a button that says "click me" and prints "clicked" to the console when clicked.
         let window = imgui::Window::new(" ");
         window
@@ -52,6 +430,17 @@ impl UI {
                 let mut clip_history = ClipboardHistory::get_instance();
                 let mut config = Config::get_instance();
 
+                // Nothing but the unlock prompt is shown until the history is unlocked
+                if clip_history.is_locked() {
+                    self.draw_unlock_prompt(&ui, &mut clip_history, &config);
+                    return;
+                }
+                // Same for the window lock, while clips are still captured
+                if window_locked {
+                    self.draw_window_unlock_prompt(&ui, &config);
+                    return;
+                }
+
                 if let Some(menu_bar_token) = ui.begin_menu_bar() {
                     if let Some(menu_token) = ui.begin_menu("File") {
                         let menu_item = imgui::MenuItem::new("Start on Startup")
@@ -65,6 +454,19 @@ impl UI {
                             // Refresh the UI
                             *control_flow = ControlFlow::Poll;
                         }
+                        if imgui::MenuItem::new("Export...").build(&ui) {
+                            self.open_export_dialog(false);
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+                        let lock_menu_item = imgui::MenuItem::new("Lock Window")
+                            .enabled(config.has_lock_passphrase());
+                        if lock_menu_item.build(&ui) {
+                            WindowLock::get_instance().lock();
+                            clip_history.clear_undo();
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
                         if imgui::MenuItem::new("Exit").build(&ui) {
                             // Exit application
                             *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
@@ -74,6 +476,77 @@ impl UI {
                     }
 
                     if let Some(menu_token) = ui.begin_menu("Edit") {
+                        let undo_label = match clip_history.undo_action() {
+                            Some(action) => format!("Undo {}", action),
+                            None => "Undo".to_string(),
+                        };
+                        let undo_menu_item = imgui::MenuItem::new(&undo_label)
+                            .shortcut(self.keymap.label(Action::Undo))
+                            .enabled(clip_history.undo_action().is_some());
+                        if undo_menu_item.build(&ui) {
+                            undo(&mut clip_history, &config, false);
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        let redo_label = match clip_history.redo_action() {
+                            Some(action) => format!("Redo {}", action),
+                            None => "Redo".to_string(),
+                        };
+                        let redo_menu_item = imgui::MenuItem::new(&redo_label)
+                            .shortcut(self.keymap.label(Action::Redo))
+                            .enabled(clip_history.redo_action().is_some());
+                        if redo_menu_item.build(&ui) {
+                            undo(&mut clip_history, &config, true);
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        ui.separator();
+                        let select_all_menu_item = imgui::MenuItem::new("Select All")
+                            .shortcut(self.keymap.label(Action::SelectAll));
+                        if select_all_menu_item.build(&ui) {
+                            self.selected_items =
+                                clip_history.items().iter().map(|item| item.id).collect();
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if let Some(separator_menu_token) = ui.begin_menu("Join Selected With") {
+                            for (label, separator) in JOIN_SEPARATORS {
+                                let separator_menu_item = imgui::MenuItem::new(label)
+                                    .selected(config.get_join_separator() == separator);
+                                if separator_menu_item.build(&ui) {
+                                    config.set_join_separator(separator);
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            separator_menu_token.end();
+                        }
+
+                        if let Some(paste_menu_token) = ui.begin_menu("Paste After Picking") {
+                            let off_menu_item = imgui::MenuItem::new("Off")
+                                .selected(config.get_auto_paste().is_none());
+                            if off_menu_item.build(&ui) {
+                                config.set_auto_paste(None);
+                                // Refresh the UI
+                                *control_flow = ControlFlow::Poll;
+                            }
+                            for chord in PasteChord::ALL {
+                                let chord_menu_item =
+                                    imgui::MenuItem::new(format!("With {}", chord.label()))
+                                        .selected(config.get_auto_paste() == Some(chord));
+                                if chord_menu_item.build(&ui) {
+                                    config.set_auto_paste(Some(chord));
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            paste_menu_token.end();
+                        }
+
+                        ui.separator();
                         let trim_clips_menu_item =
                             imgui::MenuItem::new("Trim Clips").selected(config.get_trim_clips());
                         if trim_clips_menu_item.build(&ui) {
@@ -103,18 +576,140 @@ impl UI {
                             *control_flow = ControlFlow::Poll;
                         }
 
-                        if imgui::MenuItem::new("Clear History").build(&ui) {
-                            // Clear history
-                            clip_history.clear_items();
-                            let _ = clip_history.delete_file();
+                        if imgui::MenuItem::new("Clear History...").build(&ui) {
+                            // Cleared once confirmed
+                            self.show_clear_dialog = true;
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        let show_preview_menu_item = imgui::MenuItem::new("Show Preview")
+                            .selected(config.get_show_preview());
+                        if show_preview_menu_item.build(&ui) {
+                            let new_show_preview = !config.get_show_preview();
+                            config.set_show_preview(new_show_preview);
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if imgui::MenuItem::new("Ignore Rules...").build(&ui) {
+                            self.ignore_rules_dialog = IgnoreRulesDialog {
+                                open: true,
+                                rules: config.get_ignore_rules(),
+                                error: String::new(),
+                            };
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if imgui::MenuItem::new("Keyboard Shortcuts...").build(&ui) {
+                            self.keybindings_dialog = KeybindingsDialog {
+                                open: true,
+                                fields: KeybindingsDialog::fields_of(&self.keymap),
+                                global_hotkey: config.get_global_hotkey(),
+                                hotkey_opens_popup: config.get_hotkey_opens_popup(),
+                                error: config.get_keybindings_error().unwrap_or_default(),
+                            };
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if let Some(lifetime_menu_token) = ui.begin_menu("Expire Sensitive Clips") {
+                            for (label, lifetime) in SENSITIVE_CLIP_LIFETIMES {
+                                let lifetime_menu_item = imgui::MenuItem::new(label)
+                                    .selected(config.get_sensitive_clip_lifetime() == lifetime);
+                                if lifetime_menu_item.build(&ui) {
+                                    config.set_sensitive_clip_lifetime(lifetime);
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            lifetime_menu_token.end();
+                        }
+
+                        ui.separator();
+                        let pause_menu_item =
+                            imgui::MenuItem::new("Pause Capture").selected(paused);
+                        if pause_menu_item.build(&ui) {
+                            let mut pause = Pause::get_instance();
+                            if paused {
+                                pause.resume();
+                            } else {
+                                pause.pause(None);
+                            }
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if let Some(pause_menu_token) = ui.begin_menu("Pause Capture For") {
+                            for (label, minutes) in PAUSE_DURATIONS {
+                                if imgui::MenuItem::new(label).build(&ui) {
+                                    Pause::get_instance()
+                                        .pause(Some(std::time::Duration::from_secs(minutes * 60)));
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            pause_menu_token.end();
+                        }
+
+                        ui.separator();
+                        if clip_history.is_encrypted() {
+                            if imgui::MenuItem::new("Remove Encryption").build(&ui) {
+                                clip_history.disable_encryption();
+                                if config.get_save_history() {
+                                    clip_history.save_to_file().expect(
+                                        "[Remove Encryption] Failed to save history to file",
+                                    );
+                                }
+                                // Refresh the UI
+                                *control_flow = ControlFlow::Poll;
+                            }
+                        } else if imgui::MenuItem::new("Encrypt History...").build(&ui) {
+                            self.encrypt_dialog = EncryptDialog {
+                                open: true,
+                                ..EncryptDialog::default()
+                            };
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
+
+                        if imgui::MenuItem::new("Lock Passphrase...").build(&ui) {
+                            self.lock_passphrase_dialog = LockPassphraseDialog {
+                                open: true,
+                                ..LockPassphraseDialog::default()
+                            };
                             // Refresh the UI
                             *control_flow = ControlFlow::Poll;
                         }
 
+                        if let Some(lock_menu_token) = ui.begin_menu_with_enabled(
+                            "Lock When Idle For",
+                            config.has_lock_passphrase(),
+                        ) {
+                            for (label, minutes) in LOCK_AFTER_MINUTES {
+                                let lock_menu_item = imgui::MenuItem::new(label)
+                                    .selected(config.get_lock_after_minutes() == minutes);
+                                if lock_menu_item.build(&ui) {
+                                    config.set_lock_after_minutes(minutes);
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            lock_menu_token.end();
+                        }
+
                         menu_token.end();
                     }
 
                     if let Some(menu_token) = ui.begin_menu("Help") {
+                        let help_menu_item = imgui::MenuItem::new("Keyboard Shortcuts")
+                            .shortcut(self.keymap.label(Action::ShowHelp));
+                        if help_menu_item.build(&ui) {
+                            self.show_help_dialog = true;
+                            // Refresh the UI
+                            *control_flow = ControlFlow::Poll;
+                        }
                         if imgui::MenuItem::new("About").build(&ui) {
                             self.show_about_dialog = true;
                             // Refresh the UI
@@ -127,60 +722,139 @@ impl UI {
                     menu_bar_token.end();
                 }
 
-                let mut selected_item: Option<usize> = None;
+                if let Some(error) = config.get_keybindings_error() {
+                    ui.text_colored(
+                        [0.8, 0.1, 0.1, 1.0],
+                        format!(
+                            "Invalid keybindings in preferences.json, using the defaults: {}",
+                            error
+                        ),
+                    );
+                }
 
-                let clip_history_items = clip_history.get_items();
-                for (i, item) in clip_history_items.iter().enumerate() {
-                    let selected = Some(i) == selected_item;
-                    let display_item = if item.len() > 100 {
-                        if config.get_trim_clips() {
-                            format!("{}...", item.chars().take(100).collect::<String>())
-                        } else {
-                            item.clone()
-                        }
-                    } else {
-                        item.clone()
-                    };
-                    if imgui::Selectable::new(&display_item)
-                        .selected(selected)
-                        .build(&ui)
-                    {
-                        selected_item = Some(i);
-                    }
+                ui.set_next_item_width(-1.0);
+                if SEARCH_FOCUS_REQUESTED.swap(false, Ordering::Relaxed) {
+                    self.focus_search = true;
+                }
+                if self.focus_search {
+                    ui.set_keyboard_focus_here();
+                    self.focus_search = false;
+                }
+                ui.input_text("##search", &mut self.search_query)
+                    .hint(format!(
+                        "Search ({})",
+                        self.keymap.label(Action::FocusSearch)
+                    ))
+                    .build();
 
-                    let mut clipboard = Clipboard::new().unwrap();
+                let table_sort = config.get_table_sort();
+                let rows = self.update_table_rows(&clip_history, table_sort);
+
+                // Dialogs take the keyboard while they're open
+                let focused = ui.is_window_focused_with_flags(
+                    imgui::WindowFocusedFlags::ROOT_AND_CHILD_WINDOWS,
+                );
+                let typing = ui.io().want_text_input;
+                if let Some(action) = self.keymap.pressed_action(&ui, typing).filter(|_| focused) {
+                    hide_window = self.run_action(action, &mut clip_history, &config, &rows.ids);
+                    // Refresh the UI
+                    *control_flow = ControlFlow::Poll;
+                }
 
-                    // Right-click context menu
-                    if ui.is_item_hovered() && ui.is_mouse_clicked(imgui::MouseButton::Right) {
-                        ui.open_popup(&format!("item_context_{}", i));
+                if paused {
+                    ui.text_colored(
+                        [0.8, 0.1, 0.1, 1.0],
+                        format!("{}, new copies aren't captured", pause_status),
+                    );
+                    ui.same_line();
+                    if ui.small_button("Resume") {
+                        Pause::get_instance().resume();
+                        // Refresh the UI
+                        *control_flow = ControlFlow::Poll;
                     }
+                    ui.separator();
+                }
 
-                    // Show right click context menu & copy on Click
-                    ui.popup(&format!("item_context_{}", i), || {
-                        if imgui::MenuItem::new(&"Copy").build(&ui) {
-                            clipboard.set_text(item).unwrap();
+                if !self.selected_items.is_empty()
+                    && self.draw_selection_bar(&ui, &mut clip_history, &config)
+                {
+                    // Refresh the UI
+                    *control_flow = ControlFlow::Poll;
+                }
 
-                            // Refresh the UI
-                            *control_flow = ControlFlow::Poll;
-                        }
+                // Either of them may have changed the history
+                let rows = self.update_table_rows(&clip_history, table_sort);
 
-                        if imgui::MenuItem::new(&"Remove").build(&ui) {
-                            clip_history.remove_item(i);
-                            if config.get_save_history() {
-                                clip_history
-                                    .save_to_file()
-                                    .expect("[Remove Item] Failed to save history to file");
-                            }
-                            // Refresh the UI
-                            *control_flow = ControlFlow::Poll;
-                        }
-                    });
+                // The list and the preview of the selected clip side by side,
+                // split by a handle that's dragged to resize them
+                let show_preview = config.get_show_preview();
+                let list_width = if show_preview {
+                    let available_width = ui.content_region_avail()[0] - SPLITTER_WIDTH;
+                    self.preview_width = self
+                        .preview_width
+                        .min(available_width - MIN_PANE_WIDTH)
+                        .max(MIN_PANE_WIDTH);
+                    available_width - self.preview_width
+                } else {
+                    0.0
+                };
+                let (changed, row_action) =
+                    self.draw_table(&ui, &clip_history, &mut config, &rows, list_width);
+                if changed {
+                    // Refresh the UI
+                    *control_flow = ControlFlow::Poll;
+                }
+                if let Some((index, row_action)) = row_action {
+                    hide_window |=
+                        self.run_row_action(&mut clip_history, &config, index, row_action);
+                    // Refresh the UI
+                    *control_flow = ControlFlow::Poll;
+                }
 
-                    // Copy on double click
-                    if ui.is_item_hovered() && ui.is_mouse_double_clicked(imgui::MouseButton::Left)
-                    {
-                        let _ = clipboard.set_text(item.clone());
+                if show_preview {
+                    ui.same_line_with_spacing(0.0, 0.0);
+                    ui.invisible_button(
+                        "##splitter",
+                        [SPLITTER_WIDTH, ui.content_region_avail()[1]],
+                    );
+                    if ui.is_item_hovered() || ui.is_item_active() {
+                        ui.set_mouse_cursor(Some(imgui::MouseCursor::ResizeEW));
                     }
+                    if ui.is_item_active() {
+                        self.preview_width -= ui.io().mouse_delta[0];
+                        // Refresh the UI
+                        *control_flow = ControlFlow::Poll;
+                    }
+                    if ui.is_item_deactivated() {
+                        config.set_preview_width(self.preview_width);
+                    }
+
+                    // The anchor is the clip last clicked or moved to, if it's still selected
+                    let previewed_id = self
+                        .selection_anchor
+                        .filter(|id| self.selected_items.contains(id))
+                        .or_else(|| {
+                            rows.ids
+                                .iter()
+                                .find(|id| self.selected_items.contains(id))
+                                .copied()
+                        });
+                    let previewed_item = previewed_id
+                        .and_then(|id| clip_history.index_of(id))
+                        .map(|index| clip_history.get_item(index));
+
+                    ui.same_line_with_spacing(0.0, 0.0);
+                    imgui::ChildWindow::new("##preview")
+                        .border(true)
+                        .build(&ui, || match previewed_item {
+                            Some(item) => {
+                                if self.draw_preview(&ui, item, &mut config, display, renderer) {
+                                    // Refresh the UI
+                                    *control_flow = ControlFlow::Poll;
+                                }
+                            }
+                            None => ui.text_disabled("Select a clip to preview it"),
+                        });
                 }
             });
 
@@ -209,18 +883,1842 @@ impl UI {
             *control_flow = ControlFlow::Poll;
         }
 
+        if hide_window {
+            hide_after_pick(gl_window.window(), &Config::get_instance());
+        }
+
+        if self.show_help_dialog {
+            self.draw_help_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.show_clear_dialog {
+            self.draw_clear_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        // Hidden once it has run out, which needs the UI to keep refreshing
+        if let Some(toast) = &self.toast {
+            if toast.shown_at.elapsed() < TOAST_DURATION && !window_locked {
+                self.draw_toast(&ui, [size.width as f32, size.height as f32]);
+                *control_flow = ControlFlow::Poll;
+            } else {
+                self.toast = None;
+            }
+        }
+
+        if self.export_dialog.open {
+            self.draw_export_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.keybindings_dialog.open {
+            self.draw_keybindings_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.tag_dialog.open {
+            self.draw_tag_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.encrypt_dialog.open {
+            self.draw_encrypt_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.edit_dialog.open {
+            self.draw_edit_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.lock_passphrase_dialog.open {
+            self.draw_lock_passphrase_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
+        if self.ignore_rules_dialog.open {
+            self.draw_ignore_rules_dialog(&ui);
+            *control_flow = ControlFlow::Poll;
+        }
+
         if !open {
             *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
         }
 
-        let gl_window = display.gl_window();
-        let mut target = display.draw();
-        target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
-        platform.prepare_render(&ui, gl_window.window());
-        let draw_data = ui.render();
-        renderer
-            .render(&mut target, draw_data)
-            .expect("UI rendering failed");
-        target.finish().expect("Failed to swap buffers");
+        render(ui, display, platform, renderer);
+    }
+
+    // The compact popup: a search box and the latest clips, newest first.
+    // Picking one copies it and hides the window. Returns whether anything
+    // changed, so the UI has to be refreshed.
+    fn draw_popup(
+        &mut self,
+        ui: &Ui,
+        window: &Window,
+        size: [f32; 2],
+        window_locked: bool,
+    ) -> bool {
+        let mut changed = false;
+        imgui::Window::new("##popup")
+            .size(size, Condition::Always)
+            .position([0.0, 0.0], Condition::Always)
+            .no_decoration()
+            .build(ui, || {
+                let mut clip_history = ClipboardHistory::get_instance();
+                let config = Config::get_instance();
+
+                if clip_history.is_locked() {
+                    self.draw_unlock_prompt(ui, &mut clip_history, &config);
+                    return;
+                }
+                if window_locked {
+                    self.draw_window_unlock_prompt(ui, &config);
+                    return;
+                }
+
+                // Every time it's shown, the popup starts over
+                ui.set_next_item_width(-1.0);
+                if SEARCH_FOCUS_REQUESTED.swap(false, Ordering::Relaxed) {
+                    self.popup_query.clear();
+                    self.popup_row = 0;
+                    ui.set_keyboard_focus_here();
+                }
+                if ui
+                    .input_text("##popup_search", &mut self.popup_query)
+                    .hint("Search")
+                    .build()
+                {
+                    self.popup_row = 0;
+                }
+
+                let query = self.popup_query.to_lowercase();
+                let listed: Vec<&ClipboardItem> = clip_history
+                    .items()
+                    .iter()
+                    .rev()
+                    .filter(|item| query.is_empty() || item.matches_search(&query))
+                    .take(config.get_popup_items())
+                    .collect();
+                self.popup_row = self.popup_row.min(listed.len().saturating_sub(1));
+
+                // Esc dismisses the popup, even from the search box
+                if ui.is_key_index_pressed(VirtualKeyCode::Escape as i32) {
+                    window.set_visible(false);
+                    return;
+                }
+
+                let mut picked = None;
+                let mut moved = true;
+                let typing = ui.io().want_text_input;
+                match self.keymap.pressed_action(ui, typing) {
+                    Some(Action::Up) => self.popup_row = self.popup_row.saturating_sub(1),
+                    Some(Action::Down) => {
+                        self.popup_row = (self.popup_row + 1).min(listed.len().saturating_sub(1))
+                    }
+                    Some(Action::First) => self.popup_row = 0,
+                    Some(Action::Last) => self.popup_row = listed.len().saturating_sub(1),
+                    Some(Action::CopyAndHide) => {
+                        picked = listed.get(self.popup_row).map(|item| item.id)
+                    }
+                    Some(Action::QuickCopy(n)) => picked = listed.get(n - 1).map(|item| item.id),
+                    _ => moved = false,
+                }
+                changed |= moved;
+
+                imgui::ChildWindow::new("##popup_clips")
+                    .size([0.0, -ui.frame_height_with_spacing()])
+                    .build(ui, || {
+                        for (row, item) in listed.iter().enumerate() {
+                            let text = if item.sensitive {
+                                MASKED_TEXT
+                            } else {
+                                &item.text
+                            };
+                            // One line per clip, the start of its first line
+                            let first_line = text.lines().next().unwrap_or_default();
+                            let mut label: String = first_line.chars().take(60).collect();
+                            if label.len() < text.len() {
+                                label.push_str("...");
+                            }
+                            let selected = row == self.popup_row;
+                            if imgui::Selectable::new(format!("{}##{}", label, item.id))
+                                .selected(selected)
+                                .build(ui)
+                            {
+                                picked = Some(item.id);
+                            }
+                            if selected && moved {
+                                ui.set_scroll_here_y();
+                            }
+                        }
+                        if listed.is_empty() {
+                            ui.text_disabled("No clips found");
+                        }
+                    });
+
+                ui.separator();
+                if ui.small_button("Open ClipStash") {
+                    crate::window::show_window(window);
+                    changed = true;
+                }
+
+                if let Some(id) = picked {
+                    if copy_items(&mut clip_history, &config, &HashSet::from([id])) {
+                        hide_after_pick(window, &config);
+                    }
+                }
+            });
+        changed
+    }
+
+    // Also forgets the selected items that have been removed since the rows
+    // were last worked out
+    fn update_table_rows(
+        &mut self,
+        clip_history: &ClipboardHistory,
+        sort: Option<TableSort>,
+    ) -> Rc<TableRows> {
+        let query = self.search_query.to_lowercase();
+        let rows = &self.table_rows;
+        if rows.revision == Some(clip_history.revision())
+            && rows.query == query
+            && rows.sort == sort
+        {
+            return Rc::clone(rows);
+        }
+
+        // The items matching the search, which are the ones listed
+        let items = clip_history.items();
+        let mut indices: Vec<usize> = (0..items.len())
+            .filter(|i| query.is_empty() || items[*i].matches_search(&query))
+            .collect();
+        if let Some(sort) = sort {
+            indices.sort_by(|a, b| sort.compare(&items[*a], &items[*b]));
+        }
+        let ids = indices.iter().map(|i| items[*i].id).collect();
+
+        if !self.selected_items.is_empty() {
+            let item_ids: HashSet<u64> = items.iter().map(|item| item.id).collect();
+            self.selected_items.retain(|id| item_ids.contains(id));
+        }
+        self.table_rows = Rc::new(TableRows {
+            revision: Some(clip_history.revision()),
+            query,
+            sort,
+            indices,
+            ids,
+        });
+        Rc::clone(&self.table_rows)
+    }
+
+    // The history table, which only lays out the rows in view. Returns
+    // whether anything changed, so the UI has to be refreshed, and what was
+    // done to a row, along with its index in the history.
+    fn draw_table(
+        &mut self,
+        ui: &Ui,
+        clip_history: &ClipboardHistory,
+        config: &mut Config,
+        rows: &TableRows,
+        width: f32,
+    ) -> (bool, Option<(usize, RowAction)>) {
+        let mut changed = false;
+        let mut row_action = None;
+        let Some(table_token) = ui.begin_table_with_sizing(
+            "##clips",
+            Column::ALL.len(),
+            imgui::TableFlags::RESIZABLE
+                | imgui::TableFlags::HIDEABLE
+                | imgui::TableFlags::SORTABLE
+                | imgui::TableFlags::SORT_TRISTATE
+                | imgui::TableFlags::SCROLL_Y
+                | imgui::TableFlags::BORDERS_INNER_V,
+            [width, 0.0],
+            0.0,
+        ) else {
+            return (changed, row_action);
+        };
+        self.setup_table_columns(ui, config, rows.sort);
+        ui.table_setup_scroll_freeze(0, 1);
+        ui.table_headers_row();
+
+        // Hiding, resizing and sorting are done from the headers, and saved
+        // once they're done
+        let hidden_columns: Vec<Column> = (0..Column::ALL.len())
+            .filter(|n| {
+                // Read directly, imgui-rs panics on the internal flags it doesn't know
+                let flags = unsafe { imgui::sys::igTableGetColumnFlags(*n as i32) };
+                !imgui::TableColumnFlags::from_bits_truncate(flags as u32)
+                    .contains(imgui::TableColumnFlags::IS_ENABLED)
+            })
+            .map(|n| Column::ALL[n])
+            .collect();
+        if hidden_columns != config.get_hidden_columns() {
+            config.set_hidden_columns(hidden_columns);
+        }
+        if let Some(mut sort_specs) = ui.table_sort_specs_mut() {
+            if sort_specs.should_sort() {
+                let table_sort = sort_specs.specs().iter().next().map(|spec| TableSort {
+                    column: Column::ALL[spec.column_idx()],
+                    descending: spec.sort_direction()
+                        == Some(imgui::TableSortDirection::Descending),
+                });
+                if table_sort != rows.sort {
+                    config.set_table_sort(table_sort);
+                    changed = true;
+                }
+                sort_specs.set_sorted();
+            }
+        }
+
+        // The row may be out of view, where it isn't laid out to scroll to
+        let anchor_row = self
+            .selection_anchor
+            .and_then(|anchor| rows.ids.iter().position(|id| *id == anchor));
+        if let (true, Some(row), Some((first_row_y, row_height))) =
+            (self.scroll_to_anchor, anchor_row, self.row_metrics)
+        {
+            let row_y = first_row_y + row as f32 * row_height;
+            ui.set_scroll_from_pos_y_with_ratio(row_y + row_height * 0.5 - ui.scroll_y(), 0.5);
+            self.scroll_to_anchor = false;
+        }
+
+        // Measured from the cells of the first row in view
+        let mut column_widths = config.get_column_widths();
+        let mut previous_row: Option<(usize, f32)> = None;
+
+        let mut clipper = imgui::ListClipper::new(rows.indices.len() as i32).begin(ui);
+        while clipper.step() {
+            for row in clipper.display_start() as usize..clipper.display_end() as usize {
+                let i = rows.indices[row];
+                let item = clip_history.get_item(i);
+                ui.table_next_row();
+                ui.table_next_column();
+
+                let row_y = ui.cursor_pos()[1];
+                let first_row_in_view = previous_row.is_none();
+                if let Some((previous, previous_y)) =
+                    previous_row.filter(|(previous, _)| previous + 1 == row)
+                {
+                    let row_height = row_y - previous_y;
+                    self.row_metrics =
+                        Some((previous_y - previous as f32 * row_height, row_height));
+                }
+                previous_row = Some((row, row_y));
+
+                let selected = self.selected_items.contains(&item.id);
+                let revealed = self.revealed_items.contains(&item.id);
+                let text = if item.sensitive && !revealed {
+                    MASKED_TEXT
+                } else {
+                    &item.text
+                };
+                // Rows are a single line, which the clipper needs, the preview shows the rest
+                let first_line = text.lines().next().unwrap_or_default();
+                let mut display_item: String = if config.get_trim_clips() {
+                    first_line.chars().take(100).collect()
+                } else {
+                    first_line.to_string()
+                };
+                if display_item.len() < text.trim_end().len() {
+                    display_item.push_str("...");
+                }
+                if item.sensitive {
+                    display_item = format!("[Sensitive] {}", display_item);
+                }
+                if item.pinned {
+                    display_item = format!("[Pinned] {}", display_item);
+                }
+                // Use the item id so that items with the same text don't clash
+                display_item = format!("{}##{}", display_item, item.id);
+                if imgui::Selectable::new(&display_item)
+                    .selected(selected)
+                    .span_all_columns(true)
+                    .build(ui)
+                {
+                    let io = ui.io();
+                    self.select(&rows.ids, row, io.key_ctrl, io.key_shift);
+                    changed = true;
+                }
+                // Until the rows have been measured
+                if self.scroll_to_anchor && self.selection_anchor == Some(item.id) {
+                    ui.set_scroll_here_y_with_ratio(0.5);
+                    self.scroll_to_anchor = false;
+                }
+
+                // Right-click context menu
+                if ui.is_item_hovered() && ui.is_mouse_clicked(imgui::MouseButton::Right) {
+                    ui.open_popup(format!("item_context_{}", item.id));
+                }
+
+                // Show right click context menu & copy on Click
+                ui.popup(format!("item_context_{}", item.id), || {
+                    if imgui::MenuItem::new("Copy").build(ui) {
+                        row_action = Some((i, RowAction::Copy));
+                    }
+
+                    // Actions for what the clip is, once it can be seen
+                    if !item.sensitive || revealed {
+                        if let Some(open_label) = item.content_type.open_label() {
+                            let open_menu_item = imgui::MenuItem::new(open_label)
+                                .enabled(item.content_type.open_target(&item.text).is_some());
+                            if open_menu_item.build(ui) {
+                                row_action = Some((i, RowAction::Open));
+                            }
+                        }
+                        if let Some(color) = (item.content_type == ContentType::Color)
+                            .then(|| content::parse_color(&item.text))
+                            .flatten()
+                        {
+                            ui.menu("Color Swatch", || {
+                                imgui::ColorButton::new("##color_swatch", color)
+                                    .tooltip(false)
+                                    .size([96.0, 96.0])
+                                    .build(ui);
+                                ui.text(item.text.trim());
+                            });
+                        }
+                        if item.content_type == ContentType::Json
+                            && imgui::MenuItem::new("Pretty-Print JSON").build(ui)
+                        {
+                            row_action = Some((i, RowAction::PrettyPrintJson));
+                        }
+                    }
+
+                    let pin_label = if item.pinned { "Unpin" } else { "Pin" };
+                    if imgui::MenuItem::new(pin_label).build(ui) {
+                        row_action = Some((i, RowAction::SetPinned(!item.pinned)));
+                    }
+
+                    // Sensitive items can only be edited once they're revealed,
+                    // and images not at all
+                    let edit_menu_item = imgui::MenuItem::new("Edit...")
+                        .enabled((!item.sensitive || revealed) && item.image.is_none());
+                    if edit_menu_item.build(ui) {
+                        self.edit_dialog = EditDialog {
+                            open: true,
+                            id: item.id,
+                            text: item.text.clone(),
+                            original_text: item.original_text().to_string(),
+                            revision_count: item.revisions.len(),
+                            error: String::new(),
+                        };
+                        changed = true;
+                    }
+
+                    // Moving is in history order, which a sorted table doesn't show
+                    let move_up_menu_item =
+                        imgui::MenuItem::new("Move Up").enabled(i > 0 && rows.sort.is_none());
+                    if move_up_menu_item.build(ui) {
+                        row_action = Some((i, RowAction::MoveUp));
+                    }
+
+                    let has_next_item = i + 1 < clip_history.items().len();
+                    let move_down_menu_item = imgui::MenuItem::new("Move Down")
+                        .enabled(has_next_item && rows.sort.is_none());
+                    if move_down_menu_item.build(ui) {
+                        row_action = Some((i, RowAction::MoveDown));
+                    }
+
+                    let merge_menu_item =
+                        imgui::MenuItem::new("Merge With Next").enabled(has_next_item);
+                    if merge_menu_item.build(ui) {
+                        row_action = Some((i, RowAction::MergeWithNext));
+                    }
+
+                    if item.sensitive {
+                        let reveal_label = if revealed { "Hide" } else { "Reveal" };
+                        if imgui::MenuItem::new(reveal_label).build(ui) {
+                            if revealed {
+                                self.revealed_items.remove(&item.id);
+                            } else {
+                                self.revealed_items.insert(item.id);
+                            }
+                            changed = true;
+                        }
+                    }
+
+                    let sensitive_label = if item.sensitive {
+                        "Mark as Not Sensitive"
+                    } else {
+                        "Mark as Sensitive"
+                    };
+                    if imgui::MenuItem::new(sensitive_label).build(ui) {
+                        row_action = Some((i, RowAction::SetSensitive(!item.sensitive)));
+                    }
+
+                    if imgui::MenuItem::new("Remove").build(ui) {
+                        row_action = Some((i, RowAction::Remove));
+                    }
+                });
+
+                // Copy on double click, which is a pick when it's pasted too
+                if ui.is_item_hovered() && ui.is_mouse_double_clicked(imgui::MouseButton::Left) {
+                    row_action = Some((i, RowAction::Pick));
+                }
+
+                for column in &Column::ALL[1..] {
+                    // Hidden columns are skipped
+                    if !ui.table_next_column() {
+                        continue;
+                    }
+                    if first_row_in_view {
+                        column_widths.insert(*column, ui.content_region_avail()[0].round());
+                    }
+                    match column {
+                        // Sensitive items don't give away what they are
+                        Column::Kind if !item.sensitive || revealed => {
+                            if let Some(color) = (item.content_type == ContentType::Color)
+                                .then(|| content::parse_color(&item.text))
+                                .flatten()
+                            {
+                                let size = ui.text_line_height();
+                                imgui::ColorButton::new(format!("##swatch_{}", item.id), color)
+                                    .tooltip(false)
+                                    .size([size, size])
+                                    .build(ui);
+                                ui.same_line();
+                            }
+                            ui.text(item.content_type.label())
+                        }
+                        // Nor how long they are
+                        Column::Size if !item.sensitive || revealed => {
+                            ui.text(table::format_size(item.byte_count()))
+                        }
+                        Column::Copied if item.created_at != 0 => {
+                            ui.text(format_time(item.created_at))
+                        }
+                        Column::CopyCount => ui.text(item.copy_count.to_string()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if column_widths != config.get_column_widths()
+            && !ui.is_mouse_down(imgui::MouseButton::Left)
+        {
+            config.set_column_widths(column_widths);
+        }
+        table_token.end();
+        (changed, row_action)
+    }
+
+    // Returns whether the window has to be hidden, after a pick
+    fn run_row_action(
+        &mut self,
+        clip_history: &mut ClipboardHistory,
+        config: &Config,
+        index: usize,
+        row_action: RowAction,
+    ) -> bool {
+        let id = clip_history.get_item(index).id;
+        match row_action {
+            RowAction::Copy => {
+                copy_items(clip_history, config, &HashSet::from([id]));
+            }
+            RowAction::Pick => {
+                let copied = copy_items(clip_history, config, &HashSet::from([id]));
+                return copied && config.get_auto_paste().is_some();
+            }
+            RowAction::Open => {
+                let item = clip_history.get_item(index);
+                if let Some(target) = item.content_type.open_target(&item.text) {
+                    if let Err(e) = platform::open(&target) {
+                        self.toast = Some(Toast::new(&e));
+                    }
+                }
+            }
+            RowAction::PrettyPrintJson => {
+                let Some(pretty) = content::pretty_json(&clip_history.get_item(index).text) else {
+                    return false;
+                };
+                // An edit like any other, which can be undone
+                if clip_history.edit_item(index, pretty) {
+                    if config.get_save_history() {
+                        clip_history
+                            .save_to_file()
+                            .expect("[Pretty-Print JSON] Failed to save history to file");
+                    }
+                    self.toast = Some(Toast::new("JSON pretty-printed"));
+                }
+            }
+            RowAction::SetPinned(pinned) => {
+                clip_history.set_pinned(index, pinned);
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Pin Item] Failed to save history to file");
+                }
+            }
+            RowAction::MoveUp | RowAction::MoveDown => {
+                let to = match row_action {
+                    RowAction::MoveUp => index - 1,
+                    _ => index + 1,
+                };
+                clip_history.move_item(index, to);
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Move Item] Failed to save history to file");
+                }
+            }
+            RowAction::MergeWithNext => {
+                clip_history.merge_with_next(index);
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Merge Items] Failed to save history to file");
+                }
+                self.toast = Some(Toast::new("Clips merged"));
+            }
+            RowAction::SetSensitive(sensitive) => {
+                clip_history.set_sensitive(index, sensitive);
+                self.revealed_items.remove(&id);
+                // Sensitive items are taken out of the history file
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Mark Item] Failed to save history to file");
+                }
+            }
+            RowAction::Remove => {
+                clip_history.remove_item(index);
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Remove Item] Failed to save history to file");
+                }
+                self.toast = Some(Toast::new("Clip removed"));
+            }
+        }
+        false
+    }
+
+    // Restores the columns' widths, visibility and sort from the preferences
+    fn setup_table_columns(&mut self, ui: &Ui, config: &Config, table_sort: Option<TableSort>) {
+        let hidden_columns = config.get_hidden_columns();
+        let column_widths = config.get_column_widths();
+        for column in Column::ALL {
+            let mut flags = match column {
+                Column::Content => {
+                    imgui::TableColumnFlags::WIDTH_STRETCH | imgui::TableColumnFlags::NO_HIDE
+                }
+                // Biggest, newest and most copied first
+                Column::Size | Column::Copied | Column::CopyCount => {
+                    imgui::TableColumnFlags::WIDTH_FIXED
+                        | imgui::TableColumnFlags::PREFER_SORT_DESCENDING
+                }
+                Column::Kind => imgui::TableColumnFlags::WIDTH_FIXED,
+            };
+            if !self.table_created {
+                if hidden_columns.contains(&column) {
+                    flags |= imgui::TableColumnFlags::DEFAULT_HIDE;
+                }
+                if let Some(sort) = table_sort.filter(|sort| sort.column == column) {
+                    flags |= imgui::TableColumnFlags::DEFAULT_SORT;
+                    flags.set(
+                        imgui::TableColumnFlags::PREFER_SORT_DESCENDING,
+                        sort.descending,
+                    );
+                    flags.set(
+                        imgui::TableColumnFlags::PREFER_SORT_ASCENDING,
+                        !sort.descending,
+                    );
+                }
+            }
+            ui.table_setup_column_with(imgui::TableColumnSetup {
+                flags,
+                init_width_or_weight: column_widths
+                    .get(&column)
+                    .copied()
+                    .unwrap_or(column.default_width()),
+                ..imgui::TableColumnSetup::new(column.label())
+            });
+        }
+        self.table_created = true;
+    }
+
+    // The whole clip with its line numbers, or the image, below what's known
+    // about it. Returns whether anything changed, so the UI has to be refreshed.
+    fn draw_preview(
+        &mut self,
+        ui: &Ui,
+        item: &ClipboardItem,
+        config: &mut Config,
+        display: &Display,
+        renderer: &mut Renderer,
+    ) -> bool {
+        let mut changed = false;
+        let masked = item.sensitive && !self.revealed_items.contains(&item.id);
+
+        match &item.image {
+            Some(image) => ui.text_disabled(format!(
+                "{}×{} pixels, {} bytes as PNG",
+                image.width,
+                image.height,
+                image.byte_count()
+            )),
+            // The length of a secret gives it away in part
+            None if masked => {}
+            None => ui.text_disabled(format!(
+                "{} bytes, {} characters, {} lines",
+                item.text.len(),
+                item.text.chars().count(),
+                item.text.lines().count().max(1)
+            )),
+        }
+        if item.created_at != 0 {
+            ui.text_disabled(format!("Copied {}", format_time(item.created_at)));
+        }
+        if !item.source.is_empty() {
+            ui.text_disabled(format!("From {}", item.source));
+        }
+        if !item.revisions.is_empty() {
+            ui.text_disabled(format!("Edited {} times", item.revisions.len()));
+        }
+        if item.copy_count > 0 {
+            ui.text_disabled(format!(
+                "Copied from ClipStash {} times, last {}",
+                item.copy_count,
+                format_time(item.last_copied_at)
+            ));
+        }
+        if !item.tags.is_empty() {
+            ui.text_disabled(format!("Tags: {}", item.tags.join(", ")));
+        }
+
+        if item.image.is_none() {
+            let mut word_wrap = config.get_preview_word_wrap();
+            if ui.checkbox("Word Wrap", &mut word_wrap) {
+                config.set_preview_word_wrap(word_wrap);
+                changed = true;
+            }
+            ui.same_line();
+            let mut highlight = config.get_preview_highlight();
+            if ui.checkbox("Syntax Highlighting", &mut highlight) {
+                config.set_preview_highlight(highlight);
+                changed = true;
+            }
+            if highlight && !masked {
+                if let Some(language) = self.preview_highlight(item).language {
+                    ui.same_line();
+                    ui.text_disabled(language.label());
+                }
+            }
+        }
+        ui.separator();
+
+        if let Some(image) = &item.image {
+            match self.preview_texture(item, display, renderer) {
+                Some(texture_id) => {
+                    // Scaled down to the width of the pane, never up
+                    let scale = (ui.content_region_avail()[0] / image.width as f32).min(1.0);
+                    let size = [image.width as f32 * scale, image.height as f32 * scale];
+                    imgui::Image::new(texture_id, size).build(ui);
+                }
+                None => ui.text_colored([0.8, 0.1, 0.1, 1.0], "The image couldn't be loaded"),
+            }
+            return changed;
+        }
+
+        let text = if masked { MASKED_TEXT } else { &item.text };
+        let lines: Vec<&str> = text.lines().collect();
+        let number_width = lines.len().to_string().len();
+        let word_wrap = config.get_preview_word_wrap();
+        let theme = highlight::theme();
+        let tokens: &[Vec<Token>] = if config.get_preview_highlight() && !masked {
+            &self.preview_highlight(item).lines
+        } else {
+            &[]
+        };
+        imgui::ChildWindow::new("##preview_text")
+            .horizontal_scrollbar(!word_wrap)
+            .build(ui, || {
+                let draw_line = |number: usize, line: &str| {
+                    ui.text_disabled(format!("{:>width$}", number + 1, width = number_width));
+                    ui.same_line();
+                    // Tokens can't be wrapped, so lines too long for the pane are
+                    // wrapped without colors
+                    match tokens.get(number) {
+                        Some(line_tokens)
+                            if !word_wrap
+                                || ui.calc_text_size(line)[0] <= ui.content_region_avail()[0] =>
+                        {
+                            draw_tokens(ui, line, line_tokens, &theme)
+                        }
+                        _ if word_wrap => ui.text_wrapped(line),
+                        _ => ui.text(line),
+                    }
+                };
+                if word_wrap {
+                    // Wrapped lines have different heights, so they're all laid out
+                    for (number, line) in lines.iter().enumerate() {
+                        draw_line(number, line);
+                    }
+                } else {
+                    // Only the lines in view are laid out, which keeps long clips fast
+                    let mut clipper = imgui::ListClipper::new(lines.len() as i32)
+                        .items_height(ui.text_line_height_with_spacing())
+                        .begin(ui);
+                    while clipper.step() {
+                        for number in clipper.display_start()..clipper.display_end() {
+                            draw_line(number as usize, lines[number as usize]);
+                        }
+                    }
+                }
+            });
+        changed
+    }
+
+    // Detects the language of the previewed text and splits it into tokens
+    // the first time it's previewed and after it's edited
+    fn preview_highlight(&mut self, item: &ClipboardItem) -> &Highlight {
+        let key = (item.id, item.revisions.len(), item.text.len());
+        if self.preview_highlight.as_ref().map(|(cached, _)| *cached) != Some(key) {
+            self.preview_highlight = Some((key, Highlight::new(&item.text)));
+        }
+        &self.preview_highlight.as_ref().unwrap().1
+    }
+
+    // Uploads the item's image the first time it's previewed, and drops the
+    // texture of the image previewed before
+    fn preview_texture(
+        &mut self,
+        item: &ClipboardItem,
+        display: &Display,
+        renderer: &mut Renderer,
+    ) -> Option<TextureId> {
+        if let Some((id, texture_id)) = self.preview_texture {
+            if id == item.id {
+                return Some(texture_id);
+            }
+            renderer.textures().remove(texture_id);
+            self.preview_texture = None;
+        }
+
+        let image = item.image.as_ref()?;
+        let rgba = image.to_rgba()?;
+        let raw_image =
+            glium::texture::RawImage2d::from_raw_rgba(rgba, (image.width, image.height));
+        let texture = glium::Texture2d::new(display, raw_image).ok()?;
+        let texture_id = renderer.textures().insert(imgui_glium_renderer::Texture {
+            texture: Rc::new(texture),
+            sampler: Default::default(),
+        });
+        self.preview_texture = Some((item.id, texture_id));
+        Some(texture_id)
+    }
+
+    // Nothing may stay on screen while the window is locked
+    fn close_dialogs(&mut self) {
+        self.show_about_dialog = false;
+        self.show_help_dialog = false;
+        self.show_clear_dialog = false;
+        self.export_dialog.open = false;
+        self.encrypt_dialog.close();
+        self.edit_dialog.close();
+        self.tag_dialog = TagDialog::default();
+        self.keybindings_dialog = KeybindingsDialog::default();
+        self.lock_passphrase_dialog.close();
+        self.ignore_rules_dialog = IgnoreRulesDialog::default();
+        self.revealed_items.clear();
+    }
+
+    // Returns whether a passphrase was entered, it's left in `unlock_passphrase`
+    fn draw_passphrase_prompt(&mut self, ui: &Ui, message: &[&str]) -> bool {
+        for line in message {
+            ui.text(line);
+        }
+        ui.spacing();
+
+        let entered = ui
+            .input_text("Passphrase", &mut self.unlock_passphrase)
+            .password(true)
+            .enter_returns_true(true)
+            .build();
+        let submitted = ui.button_with_size("Unlock", [60.0, 25.0]) || entered;
+
+        if !self.unlock_error.is_empty() {
+            ui.spacing();
+            ui.text_colored([0.8, 0.1, 0.1, 1.0], &self.unlock_error);
+        }
+        submitted
+    }
+
+    fn draw_unlock_prompt(
+        &mut self,
+        ui: &Ui,
+        clip_history: &mut ClipboardHistory,
+        config: &Config,
+    ) {
+        let message = [
+            "The clipboard history is encrypted.",
+            "New clips are kept in memory until it's unlocked.",
+        ];
+        if self.draw_passphrase_prompt(ui, &message) {
+            match clip_history.unlock(&self.unlock_passphrase) {
+                Ok(()) => {
+                    self.unlock_error.clear();
+                    // Save the items copied while the history was locked
+                    if config.get_save_history() {
+                        clip_history
+                            .save_to_file()
+                            .expect("[Unlock] Failed to save history to file");
+                    }
+                }
+                Err(message) => self.unlock_error = message,
+            }
+            self.unlock_passphrase.zeroize();
+        }
+    }
+
+    fn draw_window_unlock_prompt(&mut self, ui: &Ui, config: &Config) {
+        let message = [
+            "ClipStash is locked.",
+            "New clips are still captured until it's unlocked.",
+        ];
+        if self.draw_passphrase_prompt(ui, &message) {
+            let result = WindowLock::get_instance()
+                .unlock(&self.unlock_passphrase, &config.get_lock_passphrase_hash());
+            match result {
+                Ok(()) => self.unlock_error.clear(),
+                Err(message) => self.unlock_error = message,
+            }
+            self.unlock_passphrase.zeroize();
+        }
+    }
+
+    fn open_export_dialog(&mut self, selected_only: bool) {
+        self.export_dialog.open = true;
+        self.export_dialog.selected_only = selected_only;
+        self.export_dialog.selection = self.selected_items.clone();
+        self.export_dialog.status.clear();
+    }
+
+    // Click selects only the item, Ctrl-click adds or removes it and
+    // Shift-click selects every listed item from the last clicked one
+    fn select(&mut self, visible_ids: &[u64], row: usize, ctrl: bool, shift: bool) {
+        let id = visible_ids[row];
+        let anchor_row = self
+            .selection_anchor
+            .and_then(|anchor| visible_ids.iter().position(|id| *id == anchor));
+
+        if shift {
+            if let Some(anchor_row) = anchor_row {
+                if !ctrl {
+                    self.selected_items.clear();
+                }
+                let range = anchor_row.min(row)..=anchor_row.max(row);
+                self.selected_items.extend(&visible_ids[range]);
+                return;
+            }
+        }
+
+        if ctrl {
+            if !self.selected_items.remove(&id) {
+                self.selected_items.insert(id);
+            }
+        } else {
+            self.selected_items.clear();
+            self.selected_items.insert(id);
+        }
+        self.selection_anchor = Some(id);
+    }
+
+    // Runs a keyboard shortcut, and returns whether the window should be hidden
+    fn run_action(
+        &mut self,
+        action: Action,
+        clip_history: &mut ClipboardHistory,
+        config: &Config,
+        visible_ids: &[u64],
+    ) -> bool {
+        let anchor_row = self
+            .selection_anchor
+            .and_then(|anchor| visible_ids.iter().position(|id| *id == anchor));
+        let last_row = visible_ids.len().saturating_sub(1);
+
+        // Moving without anything selected starts from the top
+        let move_to = match action {
+            Action::Up => Some(anchor_row.map_or(0, |row| row.saturating_sub(1))),
+            Action::Down => Some(anchor_row.map_or(0, |row| (row + 1).min(last_row))),
+            Action::PageUp => Some(anchor_row.map_or(0, |row| row.saturating_sub(PAGE_SIZE))),
+            Action::PageDown => Some(anchor_row.map_or(0, |row| (row + PAGE_SIZE).min(last_row))),
+            Action::First => Some(0),
+            Action::Last => Some(last_row),
+            _ => None,
+        };
+        if let Some(row) = move_to {
+            if !visible_ids.is_empty() {
+                self.select(visible_ids, row, false, false);
+                self.scroll_to_anchor = true;
+            }
+            return false;
+        }
+
+        match action {
+            // Without a selection, e.g. right after searching, the first listed item is copied
+            Action::CopyAndHide => {
+                let mut copied = self.selected_items.clone();
+                if copied.is_empty() {
+                    copied.extend(visible_ids.first());
+                }
+                return copy_items(clip_history, config, &copied);
+            }
+            Action::QuickCopy(n) => {
+                let copied: HashSet<u64> = visible_ids.get(n - 1).copied().into_iter().collect();
+                return copy_items(clip_history, config, &copied);
+            }
+            Action::Remove if !self.selected_items.is_empty() => {
+                clip_history.remove_items(&self.selected_items);
+                if config.get_save_history() {
+                    clip_history
+                        .save_to_file()
+                        .expect("[Remove Items] Failed to save history to file");
+                }
+                let message = match self.selected_items.len() {
+                    1 => "Clip removed".to_string(),
+                    count => format!("{} clips removed", count),
+                };
+                self.toast = Some(Toast::new(&message));
+                self.selected_items.clear();
+            }
+            Action::FocusSearch => self.focus_search = true,
+            Action::SelectAll => self.selected_items = visible_ids.iter().copied().collect(),
+            Action::ClearSelection => {
+                if self.selected_items.is_empty() {
+                    self.search_query.clear();
+                } else {
+                    self.selected_items.clear();
+                }
+            }
+            Action::Undo => undo(clip_history, config, false),
+            Action::Redo => undo(clip_history, config, true),
+            Action::ShowHelp => self.show_help_dialog = true,
+            _ => {}
+        }
+        false
+    }
+
+    // Bulk actions for the selected items, above the list. Returns whether
+    // one of them was used.
+    fn draw_selection_bar(
+        &mut self,
+        ui: &Ui,
+        clip_history: &mut ClipboardHistory,
+        config: &Config,
+    ) -> bool {
+        let selected: Vec<usize> = clip_history
+            .items()
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| self.selected_items.contains(&item.id))
+            .map(|(index, _)| index)
+            .collect();
+
+        let mut used = false;
+        ui.text(format!("{} selected", selected.len()));
+        ui.same_line();
+        if ui.small_button("Copy Joined") {
+            used = true;
+            copy_items(clip_history, config, &self.selected_items);
+        }
+        ui.same_line();
+        // Pins them all, unless they're all pinned already
+        let all_pinned = selected
+            .iter()
+            .all(|index| clip_history.get_item(*index).pinned);
+        if ui.small_button(if all_pinned { "Unpin" } else { "Pin" }) {
+            used = true;
+            for index in &selected {
+                clip_history.set_pinned(*index, !all_pinned);
+            }
+            if config.get_save_history() {
+                clip_history
+                    .save_to_file()
+                    .expect("[Pin Items] Failed to save history to file");
+            }
+        }
+        ui.same_line();
+        if ui.small_button("Tag...") {
+            used = true;
+            self.tag_dialog = TagDialog {
+                open: true,
+                tag: String::new(),
+            };
+        }
+        ui.same_line();
+        if ui.small_button("Export...") {
+            used = true;
+            self.open_export_dialog(true);
+        }
+        ui.same_line();
+        if ui.small_button("Delete") {
+            used = true;
+            clip_history.remove_items(&self.selected_items);
+            if config.get_save_history() {
+                clip_history
+                    .save_to_file()
+                    .expect("[Remove Items] Failed to save history to file");
+            }
+            self.toast = Some(Toast::new(&format!("{} clips removed", selected.len())));
+            self.selected_items.clear();
+        }
+        ui.same_line();
+        if ui.small_button("Clear Selection") {
+            used = true;
+            self.selected_items.clear();
+        }
+        ui.separator();
+        used
+    }
+
+    fn draw_keybindings_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Keyboard Shortcuts##settings");
+        imgui::PopupModal::new("Keyboard Shortcuts##settings")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.keybindings_dialog;
+
+                ui.text("Separate shortcuts with commas, e.g. \"Down, J\" or \"Ctrl+Shift+Z\".");
+                ui.text_disabled("Leave a field empty to remove its shortcuts.");
+                ui.spacing();
+                for (action, field) in dialog.fields.iter_mut() {
+                    ui.text(action.description());
+                    ui.same_line_with_pos(300.0);
+                    ui.set_next_item_width(200.0);
+                    ui.input_text(format!("##{}", action.name()), field).build();
+                }
+                ui.spacing();
+
+                // The hotkey is only grabbed on X11
+                #[cfg(target_os = "linux")]
+                {
+                    ui.separator();
+                    ui.text("Show or hide the window from anywhere");
+                    ui.same_line_with_pos(300.0);
+                    ui.set_next_item_width(200.0);
+                    ui.input_text("##global_hotkey", &mut dialog.global_hotkey)
+                        .hint("e.g. Ctrl+Alt+V")
+                        .build();
+                    ui.text_disabled("Only on X11. Leave it empty to disable it.");
+                    ui.checkbox(
+                        "Open the compact popup at the mouse cursor instead",
+                        &mut dialog.hotkey_opens_popup,
+                    );
+                    ui.spacing();
+                }
+
+                if ui.button_with_size("Save", [60.0, 25.0]) {
+                    let global_hotkey = dialog.global_hotkey.trim().to_string();
+                    let result = dialog
+                        .to_keymap()
+                        .and_then(|keymap| set_global_hotkey(&global_hotkey).map(|_| keymap));
+                    match result {
+                        Ok(keymap) => {
+                            {
+                                let mut config = Config::get_instance();
+                                config.set_keymap(&keymap);
+                                config.set_global_hotkey(&global_hotkey);
+                                config.set_hotkey_opens_popup(dialog.hotkey_opens_popup);
+                            }
+                            self.keymap = keymap;
+                            *dialog = KeybindingsDialog::default();
+                            ui.close_current_popup();
+                        }
+                        Err(message) => dialog.error = message,
+                    }
+                }
+                ui.same_line();
+                if ui.button_with_size("Defaults", [60.0, 25.0]) {
+                    dialog.fields = KeybindingsDialog::fields_of(&Keymap::default());
+                    dialog.error.clear();
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    *dialog = KeybindingsDialog::default();
+                    ui.close_current_popup();
+                }
+
+                if !dialog.error.is_empty() {
+                    ui.spacing();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], &dialog.error);
+                }
+            });
+    }
+
+    fn draw_tag_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Tag Selected Clips");
+        imgui::PopupModal::new("Tag Selected Clips")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let entered = ui
+                    .input_text("Tag", &mut self.tag_dialog.tag)
+                    .enter_returns_true(true)
+                    .build();
+                ui.spacing();
+
+                let tag = self.tag_dialog.tag.trim().to_string();
+                if (ui.button_with_size("Add", [60.0, 25.0]) || entered) && !tag.is_empty() {
+                    let mut clip_history = ClipboardHistory::get_instance();
+                    for id in &self.selected_items {
+                        if let Some(index) = clip_history.index_of(*id) {
+                            clip_history.add_tag(index, &tag);
+                        }
+                    }
+                    if Config::get_instance().get_save_history() {
+                        clip_history
+                            .save_to_file()
+                            .expect("[Tag Items] Failed to save history to file");
+                    }
+                    self.tag_dialog.open = false;
+                    ui.close_current_popup();
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    self.tag_dialog.open = false;
+                    ui.close_current_popup();
+                }
+            });
+    }
+
+    fn draw_help_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Keyboard Shortcuts");
+        imgui::PopupModal::new("Keyboard Shortcuts")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                for (keys, description) in self.keymap.shortcuts() {
+                    ui.text(&keys);
+                    ui.same_line_with_pos(180.0);
+                    ui.text_disabled(&description);
+                }
+                ui.spacing();
+                ui.text_disabled("Click, Ctrl+click and Shift+click select clips with the mouse.");
+                ui.spacing();
+
+                if ui.button_with_size("OK", [30.0, 25.0]) {
+                    self.show_help_dialog = false;
+                    ui.close_current_popup();
+                }
+            });
+    }
+
+    fn draw_clear_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Clear History");
+        imgui::PopupModal::new("Clear History")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                ui.text("Remove every clip except the pinned ones?");
+                ui.text_disabled("This can be undone from Edit > Undo.");
+                ui.spacing();
+
+                if ui.button_with_size("Clear", [60.0, 25.0]) {
+                    let mut clip_history = ClipboardHistory::get_instance();
+                    clip_history.clear_items();
+                    if Config::get_instance().get_save_history() {
+                        clip_history
+                            .save_to_file()
+                            .expect("[Clear History] Failed to save history to file");
+                    }
+                    self.toast = Some(Toast::new("History cleared"));
+                    self.show_clear_dialog = false;
+                    ui.close_current_popup();
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    self.show_clear_dialog = false;
+                    ui.close_current_popup();
+                }
+            });
+    }
+
+    // A small window in the bottom right corner, with a button to undo the change
+    fn draw_toast(&mut self, ui: &Ui, window_size: [f32; 2]) {
+        let Some(toast) = &self.toast else {
+            return;
+        };
+        let mut undone = false;
+        imgui::Window::new("##toast")
+            .position(
+                [window_size[0] - 10.0, window_size[1] - 10.0],
+                Condition::Always,
+            )
+            .position_pivot([1.0, 1.0])
+            .no_decoration()
+            .always_auto_resize(true)
+            .focus_on_appearing(false)
+            .build(ui, || {
+                ui.text(&toast.message);
+                ui.same_line();
+                if ui.small_button("Undo") {
+                    let mut clip_history = ClipboardHistory::get_instance();
+                    undo(&mut clip_history, &Config::get_instance(), false);
+                    undone = true;
+                }
+            });
+        if undone {
+            self.toast = None;
+        }
+    }
+
+    fn draw_edit_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Edit Clip");
+        imgui::PopupModal::new("Edit Clip")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.edit_dialog;
+
+                ui.input_text_multiline("##text", &mut dialog.text, [500.0, 250.0])
+                    .build();
+
+                // The original is kept however many times the item is edited
+                if dialog.revision_count > 0 {
+                    ui.spacing();
+                    let times = match dialog.revision_count {
+                        1 => "once".to_string(),
+                        count => format!("{} times", count),
+                    };
+                    ui.text_disabled(format!("Edited {}, the original text was:", times));
+                    ui.input_text_multiline("##original", &mut dialog.original_text, [500.0, 80.0])
+                        .read_only(true)
+                        .build();
+                    if ui.small_button("Restore Original") {
+                        dialog.text = dialog.original_text.clone();
+                    }
+                }
+                ui.spacing();
+
+                if ui.button_with_size("Save", [60.0, 25.0]) {
+                    let mut clip_history = ClipboardHistory::get_instance();
+                    match clip_history.index_of(dialog.id) {
+                        Some(index) => {
+                            let edited = clip_history.edit_item(index, dialog.text.clone());
+                            if edited {
+                                if Config::get_instance().get_save_history() {
+                                    clip_history
+                                        .save_to_file()
+                                        .expect("[Edit Item] Failed to save history to file");
+                                }
+                                self.toast = Some(Toast::new("Clip edited"));
+                            }
+                            dialog.close();
+                            ui.close_current_popup();
+                        }
+                        None => dialog.error = "The clip has been removed".to_string(),
+                    }
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    dialog.close();
+                    ui.close_current_popup();
+                }
+
+                if !dialog.error.is_empty() {
+                    ui.spacing();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], &dialog.error);
+                }
+            });
+    }
+
+    fn draw_lock_passphrase_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Lock Passphrase");
+        imgui::PopupModal::new("Lock Passphrase")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.lock_passphrase_dialog;
+                let mut config = Config::get_instance();
+                let has_passphrase = config.has_lock_passphrase();
+
+                ui.text("This passphrase unlocks the window once it's locked,");
+                ui.text("from File > Lock Window, the tray or after being idle.");
+                ui.spacing();
+                if has_passphrase {
+                    ui.input_text("Current Passphrase", &mut dialog.current)
+                        .password(true)
+                        .build();
+                }
+                ui.input_text("New Passphrase", &mut dialog.passphrase)
+                    .password(true)
+                    .build();
+                ui.input_text("Confirm Passphrase", &mut dialog.confirmation)
+                    .password(true)
+                    .build();
+                ui.spacing();
+
+                // Only checked on click, Argon2 is too slow to run every frame
+                let current_is_valid = |config: &Config, current: &str| {
+                    !has_passphrase
+                        || crypto::verify_passphrase(current, &config.get_lock_passphrase_hash())
+                };
+
+                if ui.button_with_size("Save", [60.0, 25.0]) {
+                    if !current_is_valid(&config, &dialog.current) {
+                        dialog.error = "The current passphrase is wrong".to_string();
+                    } else if dialog.passphrase.is_empty() {
+                        dialog.error = "The passphrase can't be empty".to_string();
+                    } else if dialog.passphrase != dialog.confirmation {
+                        dialog.error = "The passphrases don't match".to_string();
+                    } else {
+                        match crypto::hash_passphrase(&dialog.passphrase) {
+                            Ok(passphrase_hash) => {
+                                config.set_lock_passphrase_hash(passphrase_hash);
+                                dialog.close();
+                                ui.close_current_popup();
+                            }
+                            Err(message) => dialog.error = message,
+                        }
+                    }
+                }
+                if has_passphrase {
+                    ui.same_line();
+                    if ui.button_with_size("Remove", [60.0, 25.0]) {
+                        if current_is_valid(&config, &dialog.current) {
+                            config.set_lock_passphrase_hash(String::new());
+                            dialog.close();
+                            ui.close_current_popup();
+                        } else {
+                            dialog.error = "The current passphrase is wrong".to_string();
+                        }
+                    }
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    dialog.close();
+                    ui.close_current_popup();
+                }
+
+                if !dialog.error.is_empty() {
+                    ui.spacing();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], &dialog.error);
+                }
+            });
+    }
+
+    fn draw_encrypt_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Encrypt History");
+        imgui::PopupModal::new("Encrypt History")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.encrypt_dialog;
+
+                ui.text("The history file will be encrypted with this passphrase,");
+                ui.text("which has to be entered every time ClipStash starts.");
+                ui.text("If it's forgotten, the history can't be recovered.");
+                ui.spacing();
+                ui.input_text("Passphrase", &mut dialog.passphrase)
+                    .password(true)
+                    .build();
+                ui.input_text("Confirm Passphrase", &mut dialog.confirmation)
+                    .password(true)
+                    .build();
+                ui.spacing();
+
+                if ui.button_with_size("Encrypt", [60.0, 25.0]) {
+                    if dialog.passphrase.is_empty() {
+                        dialog.error = "The passphrase can't be empty".to_string();
+                    } else if dialog.passphrase != dialog.confirmation {
+                        dialog.error = "The passphrases don't match".to_string();
+                    } else {
+                        let mut clip_history = ClipboardHistory::get_instance();
+                        let result =
+                            clip_history
+                                .enable_encryption(&dialog.passphrase)
+                                .and_then(|_| {
+                                    if Config::get_instance().get_save_history() {
+                                        clip_history.save_to_file().map_err(|e| e.to_string())?;
+                                    }
+                                    Ok(())
+                                });
+                        match result {
+                            Ok(()) => {
+                                dialog.close();
+                                ui.close_current_popup();
+                            }
+                            Err(message) => dialog.error = message,
+                        }
+                    }
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    dialog.close();
+                    ui.close_current_popup();
+                }
+
+                if !dialog.error.is_empty() {
+                    ui.spacing();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], &dialog.error);
+                }
+            });
+    }
+
+    fn draw_ignore_rules_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Ignore Rules");
+        imgui::PopupModal::new("Ignore Rules")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.ignore_rules_dialog;
+
+                ui.text("Clipboard content matching any of these rules isn't captured.");
+                ui.spacing();
+
+                let kind_labels: Vec<&str> = IgnoreRuleKind::ALL
+                    .iter()
+                    .map(|kind| kind.label())
+                    .collect();
+                let mut removed_rule = None;
+                for (i, rule) in dialog.rules.iter_mut().enumerate() {
+                    let mut kind_index = IgnoreRuleKind::ALL
+                        .iter()
+                        .position(|kind| *kind == rule.kind)
+                        .unwrap_or(0);
+                    ui.set_next_item_width(160.0);
+                    if ui.combo_simple_string(format!("##kind{}", i), &mut kind_index, &kind_labels)
+                    {
+                        rule.kind = IgnoreRuleKind::ALL[kind_index];
+                    }
+                    ui.same_line();
+                    ui.set_next_item_width(260.0);
+                    ui.input_text(format!("##value{}", i), &mut rule.value)
+                        .build();
+                    ui.same_line();
+                    if ui.button(format!("Remove##{}", i)) {
+                        removed_rule = Some(i);
+                    }
+                }
+                if let Some(i) = removed_rule {
+                    dialog.rules.remove(i);
+                }
+
+                if dialog.rules.is_empty() {
+                    ui.text_disabled("No rules yet.");
+                }
+                if ui.button("Add Rule") {
+                    dialog
+                        .rules
+                        .push(IgnoreRule::new(IgnoreRuleKind::Pattern, ""));
+                }
+
+                ui.spacing();
+                ui.text_disabled("Patterns are regular expressions searched for in the content.");
+                ui.text_disabled("Applications are window classes or process names.");
+                ui.text_disabled("Content equal to the output of a command is ignored too.");
+                ui.spacing();
+                ui.separator();
+                ui.spacing();
+
+                if ui.button_with_size("Save", [60.0, 25.0]) {
+                    match dialog.rules.iter().try_for_each(|rule| rule.validate()) {
+                        Ok(()) => {
+                            Config::get_instance().set_ignore_rules(dialog.rules.clone());
+                            *dialog = IgnoreRulesDialog::default();
+                            ui.close_current_popup();
+                        }
+                        Err(message) => dialog.error = message,
+                    }
+                }
+                ui.same_line();
+                if ui.button_with_size("Cancel", [60.0, 25.0]) {
+                    *dialog = IgnoreRulesDialog::default();
+                    ui.close_current_popup();
+                }
+
+                if !dialog.error.is_empty() {
+                    ui.spacing();
+                    ui.text_colored([0.8, 0.1, 0.1, 1.0], &dialog.error);
+                }
+            });
+    }
+
+    fn draw_export_dialog(&mut self, ui: &Ui) {
+        ui.open_popup("Export History");
+        imgui::PopupModal::new("Export History")
+            .resizable(false)
+            .always_auto_resize(true)
+            .build(ui, || {
+                let dialog = &mut self.export_dialog;
+
+                ui.text("Format");
+                ui.radio_button("JSON Lines", &mut dialog.format, ExportFormat::JsonLines);
+                ui.same_line();
+                ui.radio_button("CSV", &mut dialog.format, ExportFormat::Csv);
+                ui.same_line();
+                ui.radio_button("Markdown", &mut dialog.format, ExportFormat::Markdown);
+                ui.spacing();
+                ui.separator();
+                ui.spacing();
+
+                ui.text("Filters (leave empty to export everything)");
+                ui.input_text("Since (YYYY-MM-DD)", &mut dialog.since)
+                    .build();
+                ui.input_text("Until (YYYY-MM-DD, exclusive)", &mut dialog.until)
+                    .build();
+                ui.input_text("Tag", &mut dialog.tag).build();
+                ui.checkbox("Pinned items only", &mut dialog.pinned_only);
+                if !dialog.selection.is_empty() {
+                    ui.checkbox(
+                        format!("Selected items only ({})", dialog.selection.len()),
+                        &mut dialog.selected_only,
+                    );
+                }
+                ui.spacing();
+                ui.separator();
+                ui.spacing();
+
+                if ui.button_with_size("Export", [60.0, 25.0]) {
+                    dialog.status = match export_to_documents(dialog) {
+                        Ok(path) => format!("Exported to {}", path),
+                        Err(message) => message,
+                    };
+                }
+                ui.same_line();
+                if ui.button_with_size("Close", [60.0, 25.0]) {
+                    dialog.open = false;
+                    ui.close_current_popup();
+                }
+
+                if !dialog.status.is_empty() {
+                    ui.spacing();
+                    ui.text_wrapped(&dialog.status);
+                }
+            });
+    }
+}
+
+// Grabs the hotkey instead of the one set before, if it changed. Empty
+// disables it, and it's only grabbed on X11.
+fn set_global_hotkey(global_hotkey: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    if global_hotkey != Config::get_instance().get_global_hotkey() {
+        crate::hotkey::set_hotkey(global_hotkey)?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = global_hotkey;
+    Ok(())
+}
+
+// Hides the window once clips are copied from it with the keyboard or the
+// popup, and pastes them into the window that was active before, if enabled
+fn hide_after_pick(window: &Window, config: &Config) {
+    window.set_visible(false);
+    if let Some(chord) = config.get_auto_paste() {
+        paste::paste(chord);
+    }
+}
+
+fn render(ui: Ui, display: &Display, platform: &mut WinitPlatform, renderer: &mut Renderer) {
+    let gl_window = display.gl_window();
+    let mut target = display.draw();
+    target.clear_color_srgb(1.0, 1.0, 1.0, 1.0);
+    platform.prepare_render(&ui, gl_window.window());
+    let draw_data = ui.render();
+    renderer
+        .render(&mut target, draw_data)
+        .expect("UI rendering failed");
+    target.finish().expect("Failed to swap buffers");
+}
+
+// Draws the tokens of a line one after the other, in the colors of the theme
+fn draw_tokens(ui: &Ui, line: &str, tokens: &[Token], theme: &Theme) {
+    if tokens.is_empty() {
+        ui.text("");
+    }
+    for (n, token) in tokens.iter().enumerate() {
+        if n > 0 {
+            ui.same_line_with_spacing(0.0, 0.0);
+        }
+        let text = &line[token.range.clone()];
+        match theme.color(token.kind) {
+            Some(color) => ui.text_colored(color, text),
+            None => ui.text(text),
+        }
+    }
+}
+
+// A timestamp in local time, for the preview
+fn format_time(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(date_time) => date_time.format("%Y-%m-%d %H:%M").to_string(),
+        None => "at an unknown time".to_string(),
+    }
+}
+
+// Copies the texts of the items, joined by the separator in the order of
+// the history, or the item itself if there's only one (which may be an
+// image), and counts the copy. Returns whether anything was copied.
+fn copy_items(clip_history: &mut ClipboardHistory, config: &Config, ids: &HashSet<u64>) -> bool {
+    let mut indices: Vec<usize> = clip_history
+        .items()
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| ids.contains(&item.id))
+        .map(|(index, _)| index)
+        .collect();
+    // Sensitive items are left out of joined copies, which would be captured
+    // back as a new item that isn't marked
+    if indices.len() > 1 {
+        indices.retain(|index| !clip_history.get_item(*index).sensitive);
+    }
+    let copied = match indices[..] {
+        [] => return false,
+        [index] => Clipboard::new()
+            .map_err(|e| e.to_string())
+            .and_then(|mut clipboard| clip_history.get_item(index).copy_to(&mut clipboard))
+            .is_ok(),
+        _ => {
+            let texts: Vec<&str> = indices
+                .iter()
+                .map(|index| clip_history.get_item(*index).text.as_str())
+                .collect();
+            let mut joined = texts.join(&config.get_join_separator());
+            let copied = Clipboard::new()
+                .and_then(|mut clipboard| clipboard.set_text(joined.clone()))
+                .is_ok();
+            joined.zeroize();
+            copied
+        }
+    };
+    if copied {
+        for index in indices {
+            clip_history.record_copy(index);
+        }
+        if config.get_save_history() {
+            clip_history
+                .save_to_file()
+                .expect("[Copy Items] Failed to save history to file");
+        }
+    }
+    copied
+}
+
+// Undoes or redoes the last change, and saves the result
+fn undo(clip_history: &mut ClipboardHistory, config: &Config, redo: bool) {
+    let action = if redo {
+        clip_history.redo()
+    } else {
+        clip_history.undo()
+    };
+    if action.is_some() && config.get_save_history() {
+        clip_history
+            .save_to_file()
+            .expect("[Undo] Failed to save history to file");
+    }
+}
+
+// Writes the export to the documents folder and returns the path of the file
+fn export_to_documents(dialog: &ExportDialog) -> Result<String, String> {
+    let parse_date = |date: &str| -> Result<Option<i64>, String> {
+        if date.trim().is_empty() {
+            Ok(None)
+        } else {
+            export::parse_date(date.trim()).map(Some)
+        }
+    };
+    let filter = ExportFilter {
+        since: parse_date(&dialog.since)?,
+        until: parse_date(&dialog.until)?,
+        tag: Some(dialog.tag.trim().to_string()).filter(|tag| !tag.is_empty()),
+        pinned_only: dialog.pinned_only,
+        ids: Some(dialog.selection.clone()).filter(|_| dialog.selected_only),
+    };
+
+    let user_dirs = directories::UserDirs::new().ok_or("Failed to find the home folder")?;
+    let export_dir = user_dirs
+        .document_dir()
+        .unwrap_or(user_dirs.home_dir())
+        .to_path_buf();
+    let file_name = format!(
+        "clipstash-export-{}.{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        dialog.format.name()
+    );
+    let path = export_dir.join(file_name);
+
+    let clip_history = ClipboardHistory::get_instance();
+    let exported = export::export(&clip_history.get_items(), dialog.format, &filter);
+    std::fs::write(&path, exported).map_err(|e| format!("Failed to export: {}", e))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auto_launch::AutoLaunchBuilder;
+
+    // Draws the table headless, the way a frame does, and prints how long it
+    // takes. Run it with `cargo test --release -- --ignored --nocapture frame_time`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn frame_time_with_100k_clips() {
+        const CLIPS: usize = 100_000;
+        const FRAMES: u32 = 100;
+
+        let dir = std::env::temp_dir().join(format!("clipstash-bench-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let mut clip_history = ClipboardHistory::empty(file_path("history.json"));
+        for n in 0..CLIPS {
+            let text = format!("Clip number {}\nwith a second line", n);
+            clip_history.add_item(ClipboardItem::new(0, text));
+        }
+        let mut config = Config::defaults(file_path("preferences.json"));
+
+        let autostart = AutoLaunchBuilder::new()
+            .set_app_name("clipstash-bench")
+            .set_app_path("clipstash")
+            .build()
+            .unwrap();
+        let mut state = UI::new(autostart);
+        let mut imgui = imgui::Context::create();
+        imgui.set_ini_filename(None);
+        imgui.io_mut().display_size = [800.0, 600.0];
+        imgui.fonts().build_rgba32_texture();
+
+        let mut draw_frame = |clip_history: &ClipboardHistory, config: &mut Config| {
+            let started_at = Instant::now();
+            let ui = imgui.frame();
+            imgui::Window::new("##bench")
+                .size([800.0, 600.0], Condition::Always)
+                .build(&ui, || {
+                    let rows = state.update_table_rows(clip_history, config.get_table_sort());
+                    state.draw_table(&ui, clip_history, config, &rows, 0.0);
+                });
+            ui.render();
+            started_at.elapsed()
+        };
+
+        println!("First frame: {:?}", draw_frame(&clip_history, &mut config));
+        let steady_frames: Duration = (0..FRAMES)
+            .map(|_| draw_frame(&clip_history, &mut config))
+            .sum();
+        let steady_frame = steady_frames / FRAMES;
+        println!("Frame: {:?}", steady_frame);
+
+        clip_history.add_item(ClipboardItem::new(0, "New clip".to_string()));
+        println!(
+            "Frame after a new clip: {:?}",
+            draw_frame(&clip_history, &mut config)
+        );
+        config.set_table_sort(Some(TableSort {
+            column: Column::Content,
+            descending: false,
+        }));
+        println!(
+            "Frame after sorting: {:?}",
+            draw_frame(&clip_history, &mut config)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        // 60 frames per second
+        assert!(steady_frame < Duration::from_micros(16_667));
     }
 }
@@ -1,8 +1,9 @@
-use glium::glutin::event_loop::ControlFlow;
-use glium::glutin::window::Icon;
-use glium::{self, Display};
+use glium::glutin::dpi::{LogicalSize, PhysicalPosition};
+use glium::glutin::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
 #[cfg(target_os = "windows")]
 use glium::glutin::platform::windows::WindowBuilderExtWindows;
+use glium::glutin::window::{Icon, Window};
+use glium::{self, Display};
 
 use image::io::Reader as ImageReader;
 use image::GenericImageView;
@@ -11,13 +12,34 @@ use imgui::{Context, FontConfig, FontSource};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::{HiDpiMode, WinitPlatform};
 
-use std::sync::mpsc;
-use std::{env, thread};
-
-use systray::Application;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
 
 use std::cell::RefCell;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::highlight;
+use crate::history::ClipboardHistory;
+use crate::paste;
+use crate::pause::Pause;
+use crate::platform;
+use crate::preferences::Config;
+use crate::tray::{self, Tray};
+use crate::window_lock::WindowLock;
+
+pub const WINDOW_TITLE: &str = "ClipStash - Clipboard Manager";
+
+const WINDOW_SIZE: LogicalSize<f64> = LogicalSize::new(800.0, 600.0);
+const POPUP_SIZE: LogicalSize<f64> = LogicalSize::new(380.0, 420.0);
+
+// Pressing the global hotkey takes the focus away from the popup (because of
+// the key grab), which hides it just before the hotkey's own message arrives
+const POPUP_REFOCUS_DELAY: Duration = Duration::from_millis(300);
+
+// Whether the window is shown as the compact popup rather than the full window
+static COMPACT: AtomicBool = AtomicBool::new(false);
 
 pub struct Skin {
     pub title_bg: [f32; 4],
@@ -30,15 +52,48 @@ pub struct Skin {
     pub header_hovered: [f32; 4],
     pub header_active: [f32; 4],
     pub frame_rounding: f32,
+    // Colors of the syntax highlighting in the preview
+    pub syntax: highlight::Theme,
     //TODO: Add more?
 }
 
 // Define an enum for the different types of messages
-enum TrayMessage {
+// the window can receive from other threads (system tray, IPC)
+pub enum WindowMessage {
     Show,
+    // Show, raise and focus the window, or hide it if it's already the active one
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    Toggle,
+    // The same for the compact popup, which is shown at the mouse cursor
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Popup,
+    // Redraw the window, e.g. after capture was paused
+    Refresh,
     Quit,
 }
 
+// Cloneable handle used by other threads to send messages to the window
+#[derive(Clone)]
+pub struct WindowSender {
+    tx: mpsc::Sender<WindowMessage>,
+    event_loop_proxy: EventLoopProxy<()>,
+}
+
+impl WindowSender {
+    fn new(tx: &mpsc::Sender<WindowMessage>, event_loop: &EventLoop<()>) -> Self {
+        Self {
+            tx: tx.clone(),
+            event_loop_proxy: event_loop.create_proxy(),
+        }
+    }
+
+    pub fn send(&self, message: WindowMessage) {
+        let _ = self.tx.send(message);
+        // The event loop waits for events, so wake it up to handle the message
+        let _ = self.event_loop_proxy.send_event(());
+    }
+}
+
 pub struct ImguiWindow {
     imgui: RefCell<imgui::Context>,
     platform: RefCell<WinitPlatform>,
@@ -51,7 +106,12 @@ pub struct ImguiWindow {
     is_mouse_in_window: bool,
     is_window_minimized: bool,
     redraw_next_frame: bool,
-    rx: mpsc::Receiver<TrayMessage>,
+    popup_hidden_at: Option<Instant>,
+    // The normal and the paused tray icon, and which of them is shown
+    tray_icon_paths: (String, String),
+    tray_shows_paused: bool,
+    tx: mpsc::Sender<WindowMessage>,
+    rx: mpsc::Receiver<WindowMessage>,
 }
 
 impl ImguiWindow {
@@ -114,18 +174,17 @@ impl ImguiWindow {
 
         let event_loop = glium::glutin::event_loop::EventLoop::new();
         let mut window_builder = glium::glutin::window::WindowBuilder::new()
-            .with_inner_size(glium::glutin::dpi::LogicalSize::new(800.0, 600.0))
+            .with_inner_size(WINDOW_SIZE)
             .with_window_icon(Some(icon.clone()))
-            .with_title("ClipStash - Clipboard Manager");
+            .with_title(WINDOW_TITLE);
 
         #[cfg(target_os = "windows")]
         {
             window_builder = window_builder.with_taskbar_icon(Some(icon));
         }
 
-        let context_builder = glium::glutin::ContextBuilder::new()
-            .with_vsync(true);
-        
+        let context_builder = glium::glutin::ContextBuilder::new().with_vsync(true);
+
         let display = RefCell::new(
             glium::Display::new(window_builder, context_builder, &event_loop).unwrap(),
         );
@@ -148,29 +207,60 @@ impl ImguiWindow {
             RefCell::new(Renderer::init(&mut *imgui.borrow_mut(), &*display.borrow()).unwrap());
         let last_frame = std::time::Instant::now();
 
-        // Create a channel for sending TrayMessage from the system tray thread to the main thread
-        let (tx, rx) = mpsc::channel::<TrayMessage>();
-        let mut app = Application::new().unwrap();
-        app.set_icon_from_file(img_path.to_str().unwrap()).unwrap(); // Use the original img_path
+        // Create a channel for sending WindowMessage from the system tray thread to the main thread
+        let (tx, rx) = mpsc::channel::<WindowMessage>();
+
+        let icon_path = img_path.to_str().unwrap().to_string();
+        let paused_icon_path = img_path
+            .with_file_name("window-paused.ico")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut tray = Tray::new(&icon_path); // Use the original img_path
 
         let tx_clone = tx.clone(); // Clone the transmitter to use in the new thread
-        let _ = app.add_menu_item("Show", move |_| {
-            let _ = tx_clone.send(TrayMessage::Show);
-            Ok::<_, systray::Error>(())
+        tray.add_item("Show", move || {
+            let _ = tx_clone.send(WindowMessage::Show);
         });
 
-        let _ = app.add_menu_item("Quit", move |_| {
-            // Send a Quit message to the main thread
-            let _ = tx.send(TrayMessage::Quit);
-            Ok::<_, systray::Error>(())
+        // The event loop brings the tray icon up to date when it's woken up
+        let pause_items = [
+            ("Pause Capture", None),
+            ("Pause for 15 Minutes", Some(Duration::from_secs(15 * 60))),
+        ];
+        for (label, duration) in pause_items {
+            // Tray callbacks have to be Sync, which the event loop proxy isn't on Windows
+            let window_sender = Mutex::new(WindowSender::new(&tx, &event_loop));
+            tray.add_item(label, move || {
+                Pause::get_instance().pause(duration);
+                window_sender.lock().unwrap().send(WindowMessage::Refresh);
+            });
+        }
+
+        let window_sender = Mutex::new(WindowSender::new(&tx, &event_loop));
+        tray.add_item("Resume Capture", move || {
+            Pause::get_instance().resume();
+            window_sender.lock().unwrap().send(WindowMessage::Refresh);
         });
 
-        // Spawn a new thread to handle the system tray
-        thread::spawn(move || {
-            app.wait_for_message()
-                .expect_err("Failed to wait for system tray message");
+        // Only does something once a lock passphrase is set
+        let window_sender = Mutex::new(WindowSender::new(&tx, &event_loop));
+        tray.add_item("Lock", move || {
+            if Config::get_instance().has_lock_passphrase() {
+                WindowLock::get_instance().lock();
+                ClipboardHistory::get_instance().clear_undo();
+                window_sender.lock().unwrap().send(WindowMessage::Refresh);
+            }
+        });
+
+        let tx_clone = tx.clone();
+        tray.add_item("Quit", move || {
+            // Send a Quit message to the main thread
+            let _ = tx_clone.send(WindowMessage::Quit);
         });
 
+        tray.show().unwrap();
+
         Self {
             imgui,
             platform,
@@ -183,10 +273,18 @@ impl ImguiWindow {
             is_mouse_in_window: false,
             is_window_minimized: false,
             redraw_next_frame: false,
+            popup_hidden_at: None,
+            tray_icon_paths: (icon_path, paused_icon_path),
+            tray_shows_paused: false,
+            tx,
             rx,
         }
     }
 
+    pub fn get_sender(&self) -> WindowSender {
+        WindowSender::new(&self.tx, &self.event_loop)
+    }
+
     pub fn set_skin(&mut self, skin: Skin) {
         self.imgui.borrow_mut().style_mut().colors[imgui::StyleColor::TitleBg as usize] =
             skin.title_bg;
@@ -207,6 +305,7 @@ impl ImguiWindow {
         self.imgui.borrow_mut().style_mut().colors[imgui::StyleColor::HeaderActive as usize] =
             skin.header_active;
         self.imgui.borrow_mut().style_mut().frame_rounding = skin.frame_rounding;
+        highlight::set_theme(skin.syntax);
     }
 
     pub fn render<F>(mut self, on_draw: F)
@@ -226,16 +325,31 @@ impl ImguiWindow {
         self.event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Wait;
 
-            // Check if there's a message from the system tray thread
+            // Check if there's a message from the system tray or IPC thread
             match self.rx.try_recv() {
-                Ok(TrayMessage::Show) => {
+                Ok(WindowMessage::Show) => {
                     // Show the window
                     self.is_window_minimized = false;
                     let display_ref = self.display.borrow();
                     let gl_window = display_ref.gl_window();
-                    gl_window.window().set_visible(true);
+                    show_window(gl_window.window());
+                }
+                Ok(message @ (WindowMessage::Toggle | WindowMessage::Popup)) => {
+                    let compact = matches!(message, WindowMessage::Popup)
+                        || Config::get_instance().get_hotkey_opens_popup();
+                    let just_hidden = self
+                        .popup_hidden_at
+                        .is_some_and(|hidden_at| hidden_at.elapsed() < POPUP_REFOCUS_DELAY);
+                    let display_ref = self.display.borrow();
+                    let gl_window = display_ref.gl_window();
+                    if !just_hidden && toggle_window(gl_window.window(), compact) {
+                        self.is_window_minimized = false;
+                        crate::ui::request_search_focus();
+                    }
                 }
-                Ok(TrayMessage::Quit) => {
+                // The event that woke the loop up already triggers a redraw
+                Ok(WindowMessage::Refresh) => {}
+                Ok(WindowMessage::Quit) => {
                     // It seems we need to wake up the window to trigger the event
                     self.is_window_minimized = false;
                     *control_flow = ControlFlow::Exit;
@@ -276,8 +390,9 @@ impl ImguiWindow {
                         self.redraw_next_frame = false;
                         *control_flow = ControlFlow::Poll;
                     } else {
-                        *control_flow = ControlFlow::Wait;
+                        *control_flow = wait_for_next_event();
                     }
+                    update_tray_icon(&mut self.tray_shows_paused, &self.tray_icon_paths);
                 }
 
                 glium::glutin::event::Event::RedrawRequested(_) => {
@@ -349,6 +464,11 @@ impl ImguiWindow {
                             glium::glutin::event::WindowEvent::CursorLeft { .. } => {
                                 self.is_mouse_in_window = false;
                             }
+                            // The popup is dismissed by clicking anywhere else
+                            glium::glutin::event::WindowEvent::Focused(false) if is_compact() => {
+                                gl_window.window().set_visible(false);
+                                self.popup_hidden_at = Some(Instant::now());
+                            }
                             glium::glutin::event::WindowEvent::CloseRequested => {
                                 *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
                             }
@@ -367,3 +487,126 @@ impl ImguiWindow {
         });
     }
 }
+
+// Runs after every turn of the event loop, which is woken up when capture is
+// paused or resumed from the tray or the command line, and when a timed pause ends
+fn update_tray_icon(shows_paused: &mut bool, (icon_path, paused_icon_path): &(String, String)) {
+    let paused = Pause::get_instance().is_paused();
+    if paused != *shows_paused {
+        *shows_paused = paused;
+        tray::set_icon(if paused { paused_icon_path } else { icon_path });
+    }
+}
+
+// Waits for the next event, or until a timed pause ends or the window has been
+// idle for long enough to lock
+fn wait_for_next_event() -> ControlFlow {
+    let lock_after_minutes = Config::get_instance().get_lock_after_minutes();
+    let (locked_now, lock_deadline) = {
+        let mut window_lock = WindowLock::get_instance();
+        // A hidden window may not be redrawn, so it's locked here too, not only by the UI
+        let locked_now = window_lock.lock_if_idle(lock_after_minutes);
+        (locked_now, window_lock.idle_deadline(lock_after_minutes))
+    };
+    if locked_now {
+        ClipboardHistory::get_instance().clear_undo();
+    }
+    let resume_deadline = Pause::get_instance().resume_deadline();
+
+    match lock_deadline.into_iter().chain(resume_deadline).min() {
+        Some(deadline) => ControlFlow::WaitUntil(deadline),
+        None => ControlFlow::Wait,
+    }
+}
+
+// Hides the window if it's the active one, otherwise shows, raises and
+// focuses it, as the compact popup or the full window. Returns whether it's shown.
+fn toggle_window(window: &Window, compact: bool) -> bool {
+    if is_active_window(window) {
+        window.set_visible(false);
+        return false;
+    }
+    if compact {
+        show_popup(window);
+    } else {
+        show_window(window);
+    }
+    true
+}
+
+pub fn is_compact() -> bool {
+    COMPACT.load(Ordering::Relaxed)
+}
+
+// Shows the full window, leaving the compact popup if it was shown as one
+pub fn show_window(window: &Window) {
+    paste::remember_active_window(window);
+    if COMPACT.swap(false, Ordering::Relaxed) {
+        window.set_always_on_top(false);
+        window.set_decorations(true);
+        window.set_inner_size(WINDOW_SIZE);
+    }
+    window.set_visible(true);
+    activate_window(window);
+}
+
+// Shows the window as a small undecorated popup, always on top and at the
+// mouse cursor, kept on the monitor the cursor is on
+fn show_popup(window: &Window) {
+    paste::remember_active_window(window);
+    COMPACT.store(true, Ordering::Relaxed);
+    window.set_decorations(false);
+    window.set_always_on_top(true);
+    window.set_inner_size(POPUP_SIZE);
+
+    if let Some((x, y)) = platform::cursor_position() {
+        let monitor = window.available_monitors().find(|monitor| {
+            let (position, size) = (monitor.position(), monitor.size());
+            (position.x..position.x + size.width as i32).contains(&x)
+                && (position.y..position.y + size.height as i32).contains(&y)
+        });
+        let (x, y) = match monitor {
+            Some(monitor) => {
+                let (position, size) = (monitor.position(), monitor.size());
+                let popup_size = POPUP_SIZE.to_physical::<i32>(monitor.scale_factor());
+                (
+                    x.min(position.x + size.width as i32 - popup_size.width),
+                    y.min(position.y + size.height as i32 - popup_size.height),
+                )
+            }
+            None => (x, y),
+        };
+        window.set_outer_position(PhysicalPosition::new(x, y));
+    }
+    window.set_visible(true);
+    activate_window(window);
+}
+
+// Whether the window manager says the window is the active one
+#[cfg(target_os = "linux")]
+fn is_active_window(window: &Window) -> bool {
+    use glium::glutin::platform::unix::WindowExtUnix;
+
+    window
+        .xlib_window()
+        .is_some_and(|id| crate::hotkey::active_window() == Some(id as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_active_window(_window: &Window) -> bool {
+    false
+}
+
+// Showing a window doesn't raise or focus it when it's already visible,
+// and there's no X11 window id to do that with on Wayland
+#[cfg(target_os = "linux")]
+fn activate_window(window: &Window) {
+    use glium::glutin::platform::unix::WindowExtUnix;
+
+    if let Some(id) = window.xlib_window() {
+        let _ = crate::hotkey::activate_window(id as u32);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn activate_window(_window: &Window) {}
@@ -0,0 +1,70 @@
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::crypto;
+
+static WINDOW_LOCK: Lazy<Mutex<WindowLock>> = Lazy::new(|| Mutex::new(WindowLock::new()));
+
+// Hides the history behind the lock passphrase set in the preferences, on
+// demand or once the window has been idle. Capture carries on while locked.
+pub struct WindowLock {
+    locked: bool,
+    last_activity: Instant,
+}
+
+impl WindowLock {
+    fn new() -> Self {
+        Self {
+            locked: false,
+            last_activity: Instant::now(),
+        }
+    }
+
+    pub fn get_instance() -> std::sync::MutexGuard<'static, WindowLock> {
+        WINDOW_LOCK.lock().unwrap()
+    }
+
+    // Callers clear the undo history as well, once this lock is released,
+    // since the history is always locked before it
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    // Called whenever the window gets some input
+    pub fn record_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    // Locks if nothing happened in the window for the given minutes, 0 never
+    // locks. Returns whether it was locked now.
+    pub fn lock_if_idle(&mut self, lock_after_minutes: u64) -> bool {
+        let idle_time = Duration::from_secs(lock_after_minutes * 60);
+        if self.locked || lock_after_minutes == 0 || self.last_activity.elapsed() < idle_time {
+            return false;
+        }
+        self.lock();
+        true
+    }
+
+    // When the window locks if nothing happens until then, if it isn't locked already
+    pub fn idle_deadline(&self, lock_after_minutes: u64) -> Option<Instant> {
+        if self.locked || lock_after_minutes == 0 {
+            return None;
+        }
+        Some(self.last_activity + Duration::from_secs(lock_after_minutes * 60))
+    }
+
+    pub fn unlock(&mut self, passphrase: &str, passphrase_hash: &str) -> Result<(), String> {
+        if !crypto::verify_passphrase(passphrase, passphrase_hash) {
+            return Err("Wrong passphrase".to_string());
+        }
+        self.locked = false;
+        self.record_activity();
+        Ok(())
+    }
+}